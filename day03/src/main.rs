@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use itertools::Itertools;
 use std::{
     collections::HashMap,
@@ -13,6 +13,47 @@ struct Args {
     data_file: String,
     #[arg(long)]
     debug: bool,
+    #[arg(long, value_enum)]
+    render: Option<RenderMode>,
+    #[arg(long)]
+    render_file: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum RenderMode {
+    Ansi,
+    Html,
+}
+
+struct SchematicNumber {
+    row: usize,
+    start_column: usize,
+    length: usize,
+    value: u32,
+    is_near_symbol: bool,
+}
+
+struct Analysis {
+    numbers: Vec<SchematicNumber>,
+    // (row, column) -> (adjacent number count, product of adjacent numbers)
+    gear_ratios: HashMap<(usize, usize), (i32, u32)>,
+}
+
+impl Analysis {
+    fn part1_total(&self) -> u32 {
+        self.numbers
+            .iter()
+            .filter(|number| number.is_near_symbol)
+            .map(|number| number.value)
+            .sum()
+    }
+
+    fn gears(&self) -> impl Iterator<Item = (&(usize, usize), u32)> {
+        self.gear_ratios
+            .iter()
+            .filter(|(_, (count, _))| *count == 2)
+            .map(|(position, (_, value))| (position, *value))
+    }
 }
 
 fn main() {
@@ -30,73 +71,225 @@ fn main() {
         .map(|line| line.chars().collect_vec())
         .collect_vec();
 
-    let mut part1_total = 0;
-    let mut gear_ratios = HashMap::new();
+    let analysis = analyze(&schematic);
+
+    if args.debug {
+        for number in analysis.numbers.iter().filter(|n| n.is_near_symbol) {
+            println!("{}", number.value);
+        }
+    }
 
-    for row in 0..schematic.len() {
-        let current_row = schematic.get(row).unwrap();
+    if let Some(mode) = args.render {
+        let rendered = match mode {
+            RenderMode::Ansi => render_ansi(&schematic, &analysis),
+            RenderMode::Html => render_html(&schematic, &analysis),
+        };
+        match &args.render_file {
+            Some(path) => std::fs::write(path, rendered).expect("Failed to write render file"),
+            None => print!("{}", rendered),
+        }
+    }
+
+    println!("Part 1: {}", analysis.part1_total());
+
+    let part2 = analysis.gears().map(|(_, ratio)| ratio).sum::<u32>();
+    println!("Part 2: {}", part2);
+}
+
+fn analyze(schematic: &[Vec<char>]) -> Analysis {
+    let mut numbers = Vec::new();
+    let mut gear_ratios: HashMap<(usize, usize), (i32, u32)> = HashMap::new();
+
+    for (row, current_row) in schematic.iter().enumerate() {
         let mut column = 0;
         while column < current_row.len() {
-            if current_row[column] >= '0' && current_row[column] <= '9' {
+            if current_row[column].is_ascii_digit() {
                 let start_column = column;
                 let mut char_count = 1;
-                let mut value = (current_row[column] as u8 - '0' as u8) as u32;
+                let mut value = current_row[column].to_digit(10).unwrap();
                 column += 1;
-                'collecting: while column < current_row.len() {
-                    if current_row[column] >= '0' && current_row[column] <= '9' {
-                        value *= 10;
-                        value += (current_row[column] as u8 - '0' as u8) as u32;
-                        char_count += 1;
-                    } else {
-                        break 'collecting;
-                    }
+                while column < current_row.len() && current_row[column].is_ascii_digit() {
+                    value *= 10;
+                    value += current_row[column].to_digit(10).unwrap();
+                    char_count += 1;
                     column += 1;
                 }
 
                 let mut is_near_symbol = false;
-                'check: for check_row in
-                    ((row as isize - 1).max(0) as usize)..(row + 2).min(schematic.len())
+                for (check_row, check_line) in schematic
+                    .iter()
+                    .enumerate()
+                    .take((row + 2).min(schematic.len()))
+                    .skip(row.saturating_sub(1))
                 {
-                    for check_column in ((start_column as isize - 1).max(0) as usize)
-                        ..(start_column + char_count + 1).min(current_row.len())
+                    for (check_column, check_value) in check_line
+                        .iter()
+                        .enumerate()
+                        .take((start_column + char_count + 1).min(check_line.len()))
+                        .skip(start_column.saturating_sub(1))
                     {
-                        // if args.debug {
-                        //     println!("Checking {}, {}", check_row, check_column);
-                        // }
-                        let check_value = schematic[check_row][check_column];
-                        if !(check_value >= '0' && check_value <= '9') && check_value != '.' {
+                        if !check_value.is_ascii_digit() && *check_value != '.' {
                             is_near_symbol = true;
                         }
-                        if check_value == '*' {
-                            if gear_ratios.contains_key(&(check_row, check_column)) {
-                                let ratio: &mut (i32, u32) =
-                                    gear_ratios.get_mut(&(check_row, check_column)).unwrap();
-                                ratio.0 += 1;
-                                ratio.1 *= value;
-                            } else {
-                                gear_ratios.insert((check_row, check_column), (1, value));
-                            }
+                        if *check_value == '*' {
+                            let ratio = gear_ratios
+                                .entry((check_row, check_column))
+                                .or_insert((0, 1));
+                            ratio.0 += 1;
+                            ratio.1 *= value;
                         }
                     }
                 }
 
-                if is_near_symbol {
-                    if args.debug {
-                        println!("{}", value);
-                    }
-                    part1_total += value;
-                }
+                numbers.push(SchematicNumber {
+                    row,
+                    start_column,
+                    length: char_count,
+                    value,
+                    is_near_symbol,
+                });
             }
             column += 1;
         }
     }
 
-    println!("Part 1: {}", part1_total);
+    Analysis {
+        numbers,
+        gear_ratios,
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Span {
+    Plain,
+    PartNumber,
+    IsolatedNumber,
+    Gear,
+}
 
-    let part2 = gear_ratios
+// Classifies every cell of the schematic so both renderers agree on what gets highlighted.
+fn classify_cells(schematic: &[Vec<char>], analysis: &Analysis) -> Vec<Vec<Span>> {
+    let mut spans = schematic
         .iter()
-        .filter(|(_, (count, _))| *count == 2)
-        .map(|(_, (_, value))| *value)
-        .sum::<u32>();
-    println!("Part 2: {}", part2);
+        .map(|row| vec![Span::Plain; row.len()])
+        .collect_vec();
+
+    for number in analysis.numbers.iter() {
+        let span = if number.is_near_symbol {
+            Span::PartNumber
+        } else {
+            Span::IsolatedNumber
+        };
+        for cell in spans[number.row]
+            .iter_mut()
+            .skip(number.start_column)
+            .take(number.length)
+        {
+            *cell = span;
+        }
+    }
+
+    for ((row, column), _) in analysis.gears() {
+        spans[*row][*column] = Span::Gear;
+    }
+
+    spans
+}
+
+fn row_gear_ratios(analysis: &Analysis, row: usize) -> Vec<(usize, u32)> {
+    analysis
+        .gears()
+        .filter(|((gear_row, _), _)| *gear_row == row)
+        .map(|((_, column), ratio)| (*column, ratio))
+        .sorted()
+        .collect_vec()
+}
+
+fn render_ansi(schematic: &[Vec<char>], analysis: &Analysis) -> String {
+    let spans = classify_cells(schematic, analysis);
+    let mut output = String::new();
+
+    for (row, line) in schematic.iter().enumerate() {
+        let mut current = Span::Plain;
+        for (column, c) in line.iter().enumerate() {
+            let span = spans[row][column];
+            if span != current {
+                output += match span {
+                    Span::Plain => "\x1b[0m",
+                    Span::PartNumber => "\x1b[0;32m",
+                    Span::IsolatedNumber => "\x1b[0;31m",
+                    Span::Gear => "\x1b[1;33m",
+                };
+                current = span;
+            }
+            output.push(*c);
+        }
+        if current != Span::Plain {
+            output += "\x1b[0m";
+        }
+
+        for (column, ratio) in row_gear_ratios(analysis, row) {
+            output += &format!("  \x1b[33m* at {} = {}\x1b[0m", column, ratio);
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+fn render_html(schematic: &[Vec<char>], analysis: &Analysis) -> String {
+    let spans = classify_cells(schematic, analysis);
+    let mut output = String::new();
+
+    output += "<!DOCTYPE html>\n<html>\n<head>\n<style>\n";
+    output += ".part { color: green; }\n";
+    output += ".isolated { color: red; }\n";
+    output += ".gear { color: goldenrod; font-weight: bold; }\n";
+    output += ".ratio { color: goldenrod; }\n";
+    output += "</style>\n</head>\n<body>\n<pre>\n";
+
+    for (row, line) in schematic.iter().enumerate() {
+        let gear_ratios: HashMap<usize, u32> = row_gear_ratios(analysis, row).into_iter().collect();
+        for (span, group) in &line
+            .iter()
+            .enumerate()
+            .chunk_by(|(column, _)| spans[row][*column])
+        {
+            let group = group.collect_vec();
+            let text: String = group.iter().map(|(_, c)| html_escape(**c)).collect();
+            match span {
+                Span::Plain => output += &text,
+                Span::PartNumber => output += &format!("<span class=\"part\">{}</span>", text),
+                Span::IsolatedNumber => {
+                    output += &format!("<span class=\"isolated\">{}</span>", text)
+                }
+                Span::Gear => {
+                    for (column, _) in group.iter() {
+                        output += &format!(
+                            "<span class=\"gear\" title=\"ratio {}\">*</span>",
+                            gear_ratios[column]
+                        );
+                    }
+                }
+            }
+        }
+
+        for (column, ratio) in row_gear_ratios(analysis, row) {
+            output += &format!("  <span class=\"ratio\">* at {} = {}</span>", column, ratio);
+        }
+        output.push('\n');
+    }
+
+    output += "</pre>\n</body>\n</html>\n";
+    output
+}
+
+fn html_escape(c: char) -> String {
+    match c {
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '&' => "&amp;".to_string(),
+        '"' => "&quot;".to_string(),
+        _ => c.to_string(),
+    }
 }
@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use itertools::Itertools;
 use std::{
     collections::HashSet,
@@ -13,6 +13,84 @@ struct Args {
     data_file: String,
     #[arg(long)]
     debug: bool,
+    #[arg(long, value_enum, default_value_t = Scoring::Doubling)]
+    scoring: Scoring,
+    // Points awarded for 0, 1, 2, ... matches when using --scoring table. Match counts past the
+    // end of the table score the last entry.
+    #[arg(long, value_delimiter = ',')]
+    score_table: Vec<usize>,
+    #[arg(long, value_enum, default_value_t = OverflowPolicy::Truncate)]
+    overflow: OverflowPolicy,
+    #[arg(long, value_enum)]
+    cascade: Option<CascadeFormat>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum Scoring {
+    // 1 point for the first match, doubled for each one after
+    Doubling,
+    // 1 point per match
+    Linear,
+    // Looked up from --score-table
+    Table,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum OverflowPolicy {
+    // Copies of cards past the end of the table are discarded
+    Truncate,
+    // Winning copies of cards past the end of the table is an error
+    Error,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum CascadeFormat {
+    Table,
+    Json,
+}
+
+struct Scratchcard {
+    id: u32,
+    matches: usize,
+}
+
+impl Scratchcard {
+    fn parse(line: &str) -> Self {
+        let line = line.replace("Card", "");
+        let (card_number, remainder) = line.trim().split(':').collect_tuple().unwrap();
+        let (winning_numbers, my_numbers) = remainder.split('|').collect_tuple().unwrap();
+        let winning_numbers: HashSet<u32> = winning_numbers
+            .split_ascii_whitespace()
+            .map(|number| number.parse::<u32>().unwrap())
+            .collect();
+        let matches = my_numbers
+            .split_ascii_whitespace()
+            .map(|number| number.parse::<u32>().unwrap())
+            .filter(|number| winning_numbers.contains(number))
+            .count();
+
+        Scratchcard {
+            id: card_number.trim().parse().unwrap(),
+            matches,
+        }
+    }
+
+    fn score(&self, scoring: Scoring, table: &[usize]) -> usize {
+        match scoring {
+            Scoring::Doubling => {
+                if self.matches == 0 {
+                    0
+                } else {
+                    2_usize.pow(self.matches as u32 - 1)
+                }
+            }
+            Scoring::Linear => self.matches,
+            Scoring::Table => *table
+                .get(self.matches)
+                .or(table.last())
+                .expect("--score-table is required for table scoring"),
+        }
+    }
 }
 
 fn main() {
@@ -27,60 +105,168 @@ fn main() {
 
     let cards = lines
         .iter()
-        .map(|line| {
-            let line = line.replace("Card", "");
-            let (card_number, remainder) = line.trim().split(":").collect_tuple().unwrap();
-            let (winning_numbers, my_numbers) = remainder.split("|").collect_tuple().unwrap();
-            let winning_numbers = winning_numbers
-                .trim()
-                .split_ascii_whitespace()
-                .map(|number| number.parse::<u32>().unwrap())
-                .sorted()
-                .collect_vec();
-            let my_numbers = my_numbers
-                .trim()
-                .split_ascii_whitespace()
-                .map(|number| number.parse::<u32>().unwrap())
-                .sorted()
-                .collect_vec();
-
-            (card_number.to_string(), winning_numbers, my_numbers)
-        })
+        .map(|line| Scratchcard::parse(line))
         .collect_vec();
 
-    let scores: Vec<usize> = cards
+    if args.debug {
+        for card in cards.iter() {
+            println!("Card {}: {} matches", card.id, card.matches);
+        }
+    }
+
+    let part1: usize = cards
         .iter()
-        .map(|(_, winning, my_numbers)| {
-            let my_numbers: HashSet<u32> = HashSet::from_iter(my_numbers.iter().cloned());
-            let winning: HashSet<u32> = HashSet::from_iter(winning.iter().cloned());
-            let count: usize = my_numbers.intersection(&winning).count();
-            if count == 0 {
-                0
-            } else {
-                (2 as usize).pow(count as u32 - 1)
-            }
-        })
-        .collect();
+        .map(|card| card.score(args.scoring, &args.score_table))
+        .sum();
+    println!("Part 1: {}", part1);
 
-    println!("Part 1: {}", scores.iter().sum::<usize>());
+    let cascade = propagate_copies(&cards, args.overflow).expect("Failed to propagate copies");
 
-    let mut cards = cards
-        .iter()
-        .map(|(_, winning_numbers, my_numbers)| (1, winning_numbers, my_numbers))
-        .collect_vec();
+    match args.cascade {
+        Some(CascadeFormat::Table) => print!("{}", cascade_table(&cards, &cascade)),
+        Some(CascadeFormat::Json) => println!("{}", cascade_json(&cards, &cascade)),
+        None => {}
+    }
 
-    for i in 0..cards.len() {
-        let my_numbers = cards[i].2;
-        let winning = cards[i].1;
-        let my_numbers: HashSet<u32> = HashSet::from_iter(my_numbers.iter().cloned());
-        let winning: HashSet<u32> = HashSet::from_iter(winning.iter().cloned());
-        let count: usize = my_numbers.intersection(&winning).count();
+    let part2: usize = cascade.copies.iter().sum();
+    println!("Part 2: {}", part2);
+}
 
-        for j in (i + 1)..(i + count + 1) {
-            cards[j].0 += cards[i].0;
+struct Cascade {
+    copies: Vec<usize>,
+    // received[j] lists (index of the winning card, copies won) for every source of card j's copies
+    received: Vec<Vec<(usize, usize)>>,
+}
+
+impl Cascade {
+    fn given(&self, card: usize) -> usize {
+        self.received
+            .iter()
+            .flatten()
+            .filter(|(source, _)| *source == card)
+            .map(|(_, copies)| copies)
+            .sum()
+    }
+
+    fn largest_contributor(&self) -> Option<(usize, usize)> {
+        (0..self.copies.len())
+            .map(|card| (card, self.given(card)))
+            .filter(|(_, given)| *given > 0)
+            .max_by_key(|(card, given)| (*given, std::cmp::Reverse(*card)))
+    }
+
+    // Number of wins in the longest run of cards each winning a copy of the next, and the card it
+    // ends on
+    fn longest_chain(&self) -> (usize, usize) {
+        let mut depths = vec![0; self.copies.len()];
+        for card in 0..self.copies.len() {
+            depths[card] = self.received[card]
+                .iter()
+                .map(|(source, _)| depths[*source] + 1)
+                .max()
+                .unwrap_or(0);
         }
+
+        depths
+            .iter()
+            .enumerate()
+            .map(|(card, depth)| (*depth, card))
+            .max_by_key(|(depth, card)| (*depth, std::cmp::Reverse(*card)))
+            .unwrap_or((0, 0))
     }
+}
 
-    let part2: usize = cards.iter().map(|(count, _, _)| count).sum();
-    println!("Part 2: {}", part2);
+fn propagate_copies(cards: &[Scratchcard], overflow: OverflowPolicy) -> Result<Cascade, String> {
+    let mut copies = vec![1; cards.len()];
+    let mut received = vec![Vec::new(); cards.len()];
+
+    for (i, card) in cards.iter().enumerate() {
+        let last_won = i + card.matches;
+        if last_won >= cards.len() && overflow == OverflowPolicy::Error {
+            return Err(format!(
+                "Card {} wins copies of {} cards past the end of the table",
+                card.id,
+                last_won + 1 - cards.len()
+            ));
+        }
+
+        for j in (i + 1)..(last_won + 1).min(cards.len()) {
+            copies[j] += copies[i];
+            received[j].push((i, copies[i]));
+        }
+    }
+
+    Ok(Cascade { copies, received })
+}
+
+fn cascade_table(cards: &[Scratchcard], cascade: &Cascade) -> String {
+    let mut output = format!(
+        "{:>6} {:>8} {:>8}  {}\n",
+        "Card", "Matches", "Copies", "Received from"
+    );
+    for (i, card) in cards.iter().enumerate() {
+        let received = cascade.received[i]
+            .iter()
+            .map(|(source, copies)| format!("{}x{}", cards[*source].id, copies))
+            .join(", ");
+        output += &format!(
+            "{:>6} {:>8} {:>8}  {}\n",
+            card.id, card.matches, cascade.copies[i], received
+        );
+    }
+
+    output += "\n";
+    if let Some((card, given)) = cascade.largest_contributor() {
+        output += &format!(
+            "Largest contributor: card {} gave {} copies\n",
+            cards[card].id, given
+        );
+    }
+    let (depth, end) = cascade.longest_chain();
+    if !cards.is_empty() {
+        output += &format!(
+            "Longest chain: {} wins deep, ending at card {}\n",
+            depth, cards[end].id
+        );
+    }
+
+    output
+}
+
+fn cascade_json(cards: &[Scratchcard], cascade: &Cascade) -> String {
+    let card_entries = cards
+        .iter()
+        .enumerate()
+        .map(|(i, card)| {
+            let received = cascade.received[i]
+                .iter()
+                .map(|(source, copies)| {
+                    format!("{{\"from\":{},\"copies\":{}}}", cards[*source].id, copies)
+                })
+                .join(",");
+            format!(
+                "{{\"id\":{},\"matches\":{},\"copies\":{},\"received\":[{}]}}",
+                card.id, card.matches, cascade.copies[i], received
+            )
+        })
+        .join(",");
+
+    let largest_contributor = match cascade.largest_contributor() {
+        Some((card, given)) => format!("{{\"id\":{},\"given\":{}}}", cards[card].id, given),
+        None => "null".to_string(),
+    };
+    let longest_chain = if cards.is_empty() {
+        "null".to_string()
+    } else {
+        let (depth, end) = cascade.longest_chain();
+        format!("{{\"depth\":{},\"end\":{}}}", depth, cards[end].id)
+    };
+
+    format!(
+        "{{\"cards\":[{}],\"total\":{},\"largest_contributor\":{},\"longest_chain\":{}}}",
+        card_entries,
+        cascade.copies.iter().sum::<usize>(),
+        largest_contributor,
+        longest_chain
+    )
 }
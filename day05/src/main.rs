@@ -1,7 +1,8 @@
 use clap::Parser;
 use itertools::Itertools;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
     fs::File,
     io::{BufRead, BufReader},
 };
@@ -13,242 +14,649 @@ struct Args {
     data_file: String,
     #[arg(long)]
     debug: bool,
+    // Category to query from, e.g. `--from soil --to humidity --start 42`. Either direction along
+    // the almanac works.
+    #[arg(long, requires = "to")]
+    from: Option<String>,
+    #[arg(long, requires = "from")]
+    to: Option<String>,
+    #[arg(long, default_value_t = 0)]
+    start: u64,
+    #[arg(long, default_value_t = 1)]
+    length: u64,
+    // Report suspicious input instead of solving
+    #[arg(long)]
+    lint: bool,
 }
 
-fn main() {
-    let args = Args::parse();
+// One `dest source length` line of an almanac map
+#[derive(Copy, Clone, Debug)]
+struct MapEntry {
+    dest_start: u64,
+    source_start: u64,
+    length: u64,
+    line: usize,
+}
 
-    let file = File::open(&args.data_file).expect("Failed to open file");
-    let reader = BufReader::new(file);
-    let lines: Vec<String> = reader
-        .lines()
-        .map(|line| line.expect("Failed to read line"))
-        .collect();
+impl MapEntry {
+    fn source_end(&self) -> u128 {
+        self.source_start as u128 + self.length as u128
+    }
 
-    let seeds = lines
-        .get(0)
-        .unwrap()
-        .split_ascii_whitespace()
-        .skip(1)
-        .map(|seed| seed.parse::<u64>().unwrap())
-        .collect_vec();
-
-    let mut source_dest_map: HashMap<String, String> = HashMap::new();
-    // (Source, Dest) -> (Source Start, (DestStart, Length))
-    let mut source_dest_value_map: HashMap<(String, String), HashMap<u64, (u64, u64)>> =
-        HashMap::new();
-
-    let mut source = "";
-    let mut dest = "";
-    for line in lines.iter().skip(1) {
-        if line.is_empty() {
-            continue;
-        }
-        if line.contains("-to-") {
-            (source, dest) = line
-                .split_ascii_whitespace()
-                .collect_vec()
-                .get(0)
-                .unwrap()
-                .split("-to-")
-                .collect_tuple()
-                .unwrap();
-            source_dest_map.insert(source.to_string(), dest.to_string());
-            source_dest_value_map.insert((source.to_string(), dest.to_string()), HashMap::new());
-            continue;
+    fn dest_end(&self) -> u128 {
+        self.dest_start as u128 + self.length as u128
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Segment {
+    start: u64,
+    offset: i128,
+}
+
+// Exclusive end of the u64 domain every RangeMap covers
+const DOMAIN_END: u128 = u64::MAX as u128 + 1;
+
+// Converts an intermediate i128/u128 result back into the u64 domain, panicking instead of
+// wrapping if it doesn't fit
+fn to_domain<T: TryInto<u64>>(value: T) -> u64 {
+    value.try_into().ok().expect("Value leaves the u64 domain")
+}
+
+// A piecewise-linear function over all of u64. Each segment covers [start, next segment's start)
+// and shifts values by its offset. Values not covered by an almanac entry map to themselves, so
+// the first segment always starts at 0.
+#[derive(Clone, Debug)]
+struct RangeMap {
+    segments: Vec<Segment>,
+}
+
+impl RangeMap {
+    fn identity() -> Self {
+        RangeMap {
+            segments: vec![Segment {
+                start: 0,
+                offset: 0,
+            }],
         }
+    }
 
-        let (dest_start, source_start, length) =
-            line.split_ascii_whitespace().collect_tuple().unwrap();
-        source_dest_value_map
-            .get_mut(&(source.to_string(), dest.to_string()))
-            .unwrap()
-            .insert(
-                source_start.parse::<u64>().unwrap(),
-                (
-                    dest_start.parse::<u64>().unwrap(),
-                    length.parse::<u64>().unwrap(),
-                ),
-            );
+    fn from_entries(entries: &[MapEntry]) -> Self {
+        let mut segments = Vec::new();
+        let mut next_start: u128 = 0;
+        for entry in entries.iter().sorted_by_key(|entry| entry.source_start) {
+            let start = (entry.source_start as u128).max(next_start);
+            let end = entry.source_start as u128 + entry.length as u128;
+            if start >= end {
+                continue;
+            }
+            if start > next_start {
+                segments.push(Segment {
+                    start: to_domain(next_start),
+                    offset: 0,
+                });
+            }
+            segments.push(Segment {
+                start: to_domain(start),
+                offset: entry.dest_start as i128 - entry.source_start as i128,
+            });
+            next_start = end;
+        }
+        if next_start < DOMAIN_END {
+            segments.push(Segment {
+                start: to_domain(next_start),
+                offset: 0,
+            });
+        }
+
+        Self::normalized(segments)
+    }
+
+    fn normalized(mut segments: Vec<Segment>) -> Self {
+        segments.dedup_by_key(|segment| segment.offset);
+        RangeMap { segments }
+    }
+
+    fn segment_end(&self, index: usize) -> u128 {
+        self.segments
+            .get(index + 1)
+            .map(|segment| segment.start as u128)
+            .unwrap_or(DOMAIN_END)
+    }
+
+    fn segment_index(&self, value: u64) -> usize {
+        self.segments
+            .partition_point(|segment| segment.start <= value)
+            - 1
+    }
+
+    fn get(&self, value: u64) -> u64 {
+        let segment = self.segments[self.segment_index(value)];
+        to_domain(value as i128 + segment.offset)
     }
 
-    let mut lowest_location = u64::MAX;
+    // The image of [start, start + length) as a list of (start, length) ranges. Anything past
+    // the end of u64 is dropped; lint() reports ranges that run that far.
+    fn map_range(&self, start: u64, length: u64) -> Vec<(u64, u64)> {
+        let end = (start as u128 + length as u128).min(DOMAIN_END);
+        let mut output = Vec::new();
+        let mut index = self.segment_index(start);
+        let mut current = start as u128;
+        while current < end {
+            let piece_end = self.segment_end(index).min(end);
+            output.push((
+                to_domain(current as i128 + self.segments[index].offset),
+                to_domain(piece_end - current),
+            ));
+            current = piece_end;
+            index += 1;
+        }
 
-    for seed in seeds.iter() {
-        let location_value =
-            get_location_value(*seed, &source_dest_map, &source_dest_value_map, args.debug);
+        output
+    }
 
-        if location_value < lowest_location {
-            lowest_location = location_value;
+    // Every range that maps into [start, start + length), as a list of (start, length) ranges
+    fn preimage(&self, start: u64, length: u64) -> Vec<(u64, u64)> {
+        let start = start as i128;
+        let end = start + length as i128;
+        let mut output = Vec::new();
+        for (index, segment) in self.segments.iter().enumerate() {
+            let image_start = (segment.start as i128 + segment.offset).max(start);
+            let image_end = (self.segment_end(index) as i128 + segment.offset).min(end);
+            if image_start < image_end {
+                output.push((
+                    to_domain(image_start - segment.offset),
+                    to_domain(image_end - image_start),
+                ));
+            }
         }
+
+        merge_ranges(output)
     }
 
-    println!("Part 1: {}", lowest_location);
+    // The map equivalent to applying self and then next
+    fn then(&self, next: &RangeMap) -> RangeMap {
+        let mut segments = Vec::new();
+        for (index, segment) in self.segments.iter().enumerate() {
+            let end = self.segment_end(index);
+            let mut current = segment.start as u128;
+            while current < end {
+                let next_index = next.segment_index(to_domain(current as i128 + segment.offset));
+                let next_end =
+                    u128::try_from(next.segment_end(next_index) as i128 - segment.offset)
+                        .expect("Composed map leaves the u64 domain");
+                segments.push(Segment {
+                    start: to_domain(current),
+                    offset: segment.offset + next.segments[next_index].offset,
+                });
+                current = next_end.min(end);
+            }
+        }
 
-    // lowest_location = u64::MAX;
-    // let mut seed_index = 0;
-    // while seed_index < seeds.len() {
-    //     let seed_start = seeds[seed_index];
-    //     let seed_range_length = seeds[seed_index + 1];
-    //     seed_index += 2;
+        Self::normalized(segments)
+    }
+}
 
-    //     let pb = indicatif::ProgressBar::new(seed_range_length);
-    //     for seed in seed_start..(seed_start + seed_range_length) {
-    //         let location_value =
-    //             get_location_value(seed, &source_dest_map, &source_dest_value_map, args.debug);
+// Sorts (start, length) ranges and joins any that touch or overlap
+fn merge_ranges(ranges: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    let mut merged: Vec<(u64, u64)> = Vec::new();
+    for (start, length) in ranges.into_iter().sorted() {
+        if let Some((last_start, last_length)) = merged.last_mut() {
+            let last_end = *last_start as u128 + *last_length as u128;
+            if start as u128 <= last_end {
+                let end = last_end.max(start as u128 + length as u128);
+                *last_length = to_domain(end - *last_start as u128);
+                continue;
+            }
+        }
+        merged.push((start, length));
+    }
 
-    //         if location_value < lowest_location {
-    //             lowest_location = location_value;
-    //         }
-    //         pb.inc(1);
-    //     }
-    //     pb.finish_and_clear();
-    // }
+    merged
+}
 
-    println!(
-        "Part 2: {}",
-        fast_process_part2(&seeds, &source_dest_map, &source_dest_value_map, args.debug)
-    );
+struct AlmanacMap {
+    source: String,
+    dest: String,
+    entries: Vec<MapEntry>,
+    map: RangeMap,
+    line: usize,
 }
 
-fn get_location_value(
-    seed: u64,
-    source_dest_map: &HashMap<String, String>,
-    source_dest_value_map: &HashMap<(String, String), HashMap<u64, (u64, u64)>>,
-    debug: bool,
-) -> u64 {
-    let mut source_category = "seed";
-    let mut source_value = seed;
+enum AlmanacError {
+    MissingLink(String),
+    DuplicateMap(String),
+    Cycle(Vec<String>),
+    OverlappingEntries {
+        source: String,
+        dest: String,
+        first: MapEntry,
+        second: MapEntry,
+    },
+    EntryOutOfRange(MapEntry),
+}
 
-    if debug {
-        println!("Processing seed: {}", seed);
+impl fmt::Display for AlmanacError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AlmanacError::MissingLink(category) => {
+                write!(f, "No map leads out of {} towards location", category)
+            }
+            AlmanacError::DuplicateMap(category) => {
+                write!(f, "More than one map leads out of {}", category)
+            }
+            AlmanacError::Cycle(categories) => {
+                write!(f, "Maps form a cycle: {}", categories.join(" -> "))
+            }
+            AlmanacError::OverlappingEntries {
+                source,
+                dest,
+                first,
+                second,
+            } => write!(
+                f,
+                "line {}: {}-to-{} entry overlaps the entry on line {}",
+                second.line, source, dest, first.line
+            ),
+            AlmanacError::EntryOutOfRange(entry) => write!(
+                f,
+                "line {}: entry {} {} {} runs past {}",
+                entry.line,
+                entry.dest_start,
+                entry.source_start,
+                entry.length,
+                u64::MAX
+            ),
+        }
     }
+}
+
+struct Almanac {
+    seeds: Vec<u64>,
+    maps: Vec<AlmanacMap>,
+}
 
-    while source_category != "location" {
-        let dest_category = source_dest_map.get(source_category).unwrap();
-        let map_entry = source_dest_value_map
-            .get(&(source_category.to_string(), dest_category.to_string()))
+impl Almanac {
+    fn parse(lines: &[String]) -> Self {
+        let seeds = lines
+            .first()
             .unwrap()
-            .iter()
-            .find(|entry| *entry.0 <= source_value && (*entry.0 + entry.1 .1) > source_value);
-        if map_entry.is_none() {
-            if debug {
-                println!(
-                    "{} ({}) to {} ({})",
-                    source_category, source_value, dest_category, source_value
-                );
+            .split_ascii_whitespace()
+            .skip(1)
+            .map(|seed| seed.parse::<u64>().unwrap())
+            .collect_vec();
+
+        let mut maps: Vec<AlmanacMap> = Vec::new();
+        for (line_index, line) in lines.iter().enumerate().skip(1) {
+            if line.is_empty() {
+                continue;
             }
-        } else {
-            let map_entry = map_entry.unwrap();
-            let new_source_value = map_entry.1 .0 + (source_value - map_entry.0);
-            if debug {
-                println!(
-                    "{} ({}) to {} ({})",
-                    source_category, source_value, dest_category, new_source_value
-                );
+            if line.contains("-to-") {
+                let (source, dest) = line
+                    .split_ascii_whitespace()
+                    .next()
+                    .unwrap()
+                    .split("-to-")
+                    .collect_tuple()
+                    .unwrap();
+                maps.push(AlmanacMap {
+                    source: source.to_string(),
+                    dest: dest.to_string(),
+                    entries: Vec::new(),
+                    map: RangeMap::identity(),
+                    line: line_index + 1,
+                });
+                continue;
             }
-            source_value = new_source_value;
+
+            let (dest_start, source_start, length) = line
+                .split_ascii_whitespace()
+                .map(|value| value.parse::<u64>().unwrap())
+                .collect_tuple()
+                .unwrap();
+            maps.last_mut().unwrap().entries.push(MapEntry {
+                dest_start,
+                source_start,
+                length,
+                line: line_index + 1,
+            });
         }
-        source_category = dest_category;
+
+        for map in maps.iter_mut() {
+            map.map = RangeMap::from_entries(&map.entries);
+        }
+
+        Almanac { seeds, maps }
     }
 
-    return source_value;
-}
+    fn outgoing(&self, category: &str) -> Option<&AlmanacMap> {
+        self.maps.iter().find(|map| map.source == category)
+    }
 
-fn fast_process_part2(
-    seeds: &Vec<u64>,
-    source_dest_map: &HashMap<String, String>,
-    source_dest_value_map: &HashMap<(String, String), HashMap<u64, (u64, u64)>>,
-    debug: bool,
-) -> u64 {
-    let mut seed_index = 0;
-    let mut source_indexes = Vec::new();
-    while seed_index < seeds.len() {
-        let seed_start = seeds[seed_index];
-        let seed_range_length = seeds[seed_index + 1];
-        source_indexes.push((seed_start, seed_range_length));
-        seed_index += 2;
-    }
-
-    let mut source_type = "seed";
-    while source_type != "location" {
-        source_indexes = source_indexes
+    fn validate(&self) -> Vec<AlmanacError> {
+        let mut errors = Vec::new();
+
+        for (source, count) in self.maps.iter().counts_by(|map| map.source.as_str()) {
+            if count > 1 {
+                errors.push(AlmanacError::DuplicateMap(source.to_string()));
+            }
+        }
+
+        let path = self.seed_path();
+        let last = path.last().unwrap();
+        if *last != "location" && self.outgoing(last).is_none() {
+            errors.push(AlmanacError::MissingLink(last.to_string()));
+        }
+
+        // Follows the maps on from every category, not just seed, so cycles off the seed to
+        // location chain are caught too. Categories already walked lead nowhere new.
+        let mut walked: HashSet<&str> = HashSet::new();
+        for map in self.maps.iter() {
+            let mut path = vec![map.source.as_str()];
+            while let Some(next) = self.outgoing(path.last().unwrap()) {
+                let category = next.dest.as_str();
+                if walked.contains(category) {
+                    break;
+                }
+                if let Some(start) = path.iter().position(|seen| *seen == category) {
+                    let mut cycle = path[start..].iter().map(|c| c.to_string()).collect_vec();
+                    cycle.push(category.to_string());
+                    errors.push(AlmanacError::Cycle(cycle));
+                    break;
+                }
+                path.push(category);
+            }
+            walked.extend(path);
+        }
+
+        for map in self.maps.iter() {
+            // The entry reaching furthest so far, so nested entries are caught as well as neighbours
+            let mut furthest: Option<&MapEntry> = None;
+            for entry in map
+                .entries
+                .iter()
+                .filter(|entry| entry.length > 0)
+                .sorted_by_key(|entry| entry.source_start)
+            {
+                if let Some(first) = furthest {
+                    if first.source_end() > entry.source_start as u128 {
+                        errors.push(AlmanacError::OverlappingEntries {
+                            source: map.source.clone(),
+                            dest: map.dest.clone(),
+                            first: *first,
+                            second: *entry,
+                        });
+                    }
+                }
+                if furthest.is_none_or(|first| entry.source_end() > first.source_end()) {
+                    furthest = Some(entry);
+                }
+            }
+        }
+
+        for entry in self.maps.iter().flat_map(|map| map.entries.iter()) {
+            if entry.source_end() > DOMAIN_END || entry.dest_end() > DOMAIN_END {
+                errors.push(AlmanacError::EntryOutOfRange(*entry));
+            }
+        }
+
+        errors
+    }
+
+    // The categories passed through going from seed towards location, stopping at the first
+    // one with no map on or that has already been passed
+    fn seed_path(&self) -> Vec<&str> {
+        let mut path = vec!["seed"];
+        while let Some(map) = self.outgoing(path.last().unwrap()) {
+            if path.last() == Some(&"location") || path.contains(&map.dest.as_str()) {
+                break;
+            }
+            path.push(&map.dest);
+        }
+
+        path
+    }
+
+    // Everything validate() rejects plus input that is legal but probably a mistake
+    fn lint(&self) -> Vec<String> {
+        let mut findings = self
+            .validate()
             .iter()
-            .flat_map(|(source_start, source_length)| {
-                map_range_to_range(
-                    source_type,
-                    *source_start,
-                    *source_length,
-                    source_dest_map,
-                    source_dest_value_map,
-                    debug,
-                )
-            })
+            .map(|error| error.to_string())
             .collect_vec();
-        source_type = source_dest_map.get(source_type).unwrap();
+
+        if !self.seeds.len().is_multiple_of(2) {
+            findings.push(format!(
+                "line 1: odd number of seed values ({}), the last has no range length",
+                self.seeds.len()
+            ));
+        }
+        for (index, (start, length)) in self.seeds.iter().tuples().enumerate() {
+            if *length == 0 {
+                findings.push(format!(
+                    "line 1: seed range {} starting at {} has zero length",
+                    index + 1,
+                    start
+                ));
+            }
+            if *start as u128 + *length as u128 > DOMAIN_END {
+                findings.push(format!(
+                    "line 1: seed range {} starting at {} with length {} runs past {}",
+                    index + 1,
+                    start,
+                    length,
+                    u64::MAX
+                ));
+            }
+        }
+
+        let path = self.seed_path();
+        for map in self.maps.iter() {
+            if map.source == "location" || !path.contains(&map.source.as_str()) {
+                findings.push(format!(
+                    "line {}: {}-to-{} map is not on the way from seed to location",
+                    map.line, map.source, map.dest
+                ));
+            }
+            if map.entries.is_empty() {
+                findings.push(format!(
+                    "line {}: {}-to-{} map has no entries",
+                    map.line, map.source, map.dest
+                ));
+            }
+            for entry in map.entries.iter().filter(|entry| entry.length == 0) {
+                findings.push(format!(
+                    "line {}: {}-to-{} entry has zero length",
+                    entry.line, map.source, map.dest
+                ));
+            }
+
+            let mut covered_to: u128 = 0;
+            for entry in map
+                .entries
+                .iter()
+                .filter(|entry| entry.length > 0)
+                .sorted_by_key(|entry| entry.source_start)
+            {
+                if entry.source_start as u128 > covered_to {
+                    findings.push(format!(
+                        "line {}: {}-to-{} leaves {}..{} unmapped",
+                        map.line, map.source, map.dest, covered_to, entry.source_start
+                    ));
+                }
+                covered_to = covered_to.max(entry.source_end());
+            }
+        }
+
+        findings
     }
 
-    return source_indexes
-        .iter()
-        .map(|(start, _)| *start)
-        .min()
-        .unwrap();
-}
+    // Composes every map between the two categories into a single function
+    fn chain(&self, from: &str, to: &str) -> RangeMap {
+        let mut category = from;
+        let mut map = RangeMap::identity();
+        while category != to {
+            let next = self.outgoing(category).unwrap();
+            map = map.then(&next.map);
+            category = &next.dest;
+        }
 
-fn map_range_to_range(
-    source_category: &str,
-    source_start: u64,
-    source_length: u64,
-    source_dest_map: &HashMap<String, String>,
-    source_dest_value_map: &HashMap<(String, String), HashMap<u64, (u64, u64)>>,
-    debug: bool,
-) -> Vec<(u64, u64)> {
-    let dest_category = source_dest_map.get(source_category).unwrap();
-    let map_entries = source_dest_value_map
-        .get(&(source_category.to_string(), dest_category.to_string()))
-        .unwrap()
-        .iter()
-        .sorted_by_key(|entry| entry.0)
-        .collect_vec();
+        map
+    }
 
-    // (Source, Dest) -> (Source Start, (DestStart, Length))
+    // Shortest list of (map, is_forward) steps from one category to another, walking maps in
+    // either direction
+    fn route(&self, from: &str, to: &str) -> Option<Vec<(&AlmanacMap, bool)>> {
+        let mut previous: HashMap<&str, (&str, &AlmanacMap, bool)> = HashMap::new();
+        let mut seen = HashSet::from([from]);
+        let mut to_visit = VecDeque::from([from]);
+        while let Some(category) = to_visit.pop_front() {
+            if category == to {
+                let mut steps = Vec::new();
+                let mut current = to;
+                while current != from {
+                    let (before, map, is_forward) = previous[current];
+                    steps.push((map, is_forward));
+                    current = before;
+                }
+                steps.reverse();
+                return Some(steps);
+            }
 
-    let mut output = Vec::new();
-    let mut next_start = source_start;
-    let end = source_start + source_length;
-    for entry_index in 0..map_entries.len() {
-        let (entry_source_start, (entry_dest_start, entry_length)) = map_entries[entry_index];
+            for map in self.maps.iter() {
+                let next = if map.source == category {
+                    Some((map.dest.as_str(), true))
+                } else if map.dest == category {
+                    Some((map.source.as_str(), false))
+                } else {
+                    None
+                };
+                if let Some((next, is_forward)) = next {
+                    if seen.insert(next) {
+                        previous.insert(next, (category, map, is_forward));
+                        to_visit.push_back(next);
+                    }
+                }
+            }
+        }
 
-        if *entry_source_start > next_start {
-            output.push((
-                next_start,
-                (entry_source_start - next_start).min(end - next_start),
-            ));
-            next_start = *entry_source_start;
+        None
+    }
+
+    // All ranges in the `to` category reachable from [start, start + length) in the `from` category
+    fn query(&self, from: &str, to: &str, start: u64, length: u64) -> Option<Vec<(u64, u64)>> {
+        let mut ranges = vec![(start, length)];
+        for (map, is_forward) in self.route(from, to)? {
+            ranges = merge_ranges(
+                ranges
+                    .iter()
+                    .flat_map(|(start, length)| {
+                        if is_forward {
+                            map.map.map_range(*start, *length)
+                        } else {
+                            map.map.preimage(*start, *length)
+                        }
+                    })
+                    .collect_vec(),
+            );
         }
 
-        if next_start >= end {
-            break;
+        Some(ranges)
+    }
+
+    fn trace_seed(&self, seed: u64) {
+        println!("Processing seed: {}", seed);
+        let mut category = "seed";
+        let mut value = seed;
+        while category != "location" {
+            let next = self.outgoing(category).unwrap();
+            let next_value = next.map.get(value);
+            println!("{} ({}) to {} ({})", category, value, next.dest, next_value);
+            category = &next.dest;
+            value = next_value;
         }
+    }
+}
 
-        if *entry_source_start <= next_start && entry_source_start + entry_length > next_start {
-            let output_length = (entry_length - (next_start - entry_source_start))
-                .min(source_length - (next_start - source_start));
-            output.push((
-                entry_dest_start + next_start - entry_source_start,
-                output_length,
-            ));
-            next_start += output_length;
+fn main() {
+    let args = Args::parse();
+
+    let file = File::open(&args.data_file).expect("Failed to open file");
+    let reader = BufReader::new(file);
+    let lines: Vec<String> = reader
+        .lines()
+        .map(|line| line.expect("Failed to read line"))
+        .collect();
+
+    let almanac = Almanac::parse(&lines);
+    if args.lint {
+        for finding in almanac.lint() {
+            println!("{}", finding);
         }
+        return;
+    }
 
-        if next_start >= end {
-            break;
+    let errors = almanac.validate();
+    if !errors.is_empty() {
+        for error in errors.iter() {
+            eprintln!("{}", error);
+        }
+        std::process::exit(1);
+    }
+
+    if let (Some(from), Some(to)) = (&args.from, &args.to) {
+        match almanac.query(from, to, args.start, args.length) {
+            Some(ranges) => {
+                for (start, length) in ranges {
+                    println!(
+                        "{} {}..{} -> {} {}..{}",
+                        from,
+                        args.start,
+                        args.start as u128 + args.length as u128,
+                        to,
+                        start,
+                        start as u128 + length as u128
+                    );
+                }
+            }
+            None => eprintln!("No maps connect {} and {}", from, to),
         }
     }
 
-    if next_start < end {
-        output.push((next_start, end - next_start));
+    let seed_to_location = almanac.chain("seed", "location");
+
+    if args.debug {
+        for seed in almanac.seeds.iter() {
+            almanac.trace_seed(*seed);
+        }
+        println!(
+            "seed-to-location has {} segments",
+            seed_to_location.segments.len()
+        );
     }
 
-    output
+    let part1 = almanac
+        .seeds
+        .iter()
+        .map(|seed| seed_to_location.get(*seed))
+        .min()
+        .unwrap();
+    println!("Part 1: {}", part1);
+
+    if !almanac.seeds.len().is_multiple_of(2) {
+        eprintln!(
+            "Part 2: seed list has an odd number of values ({})",
+            almanac.seeds.len()
+        );
+        return;
+    }
+    let part2 = almanac
+        .seeds
+        .iter()
+        .tuples()
+        .flat_map(|(start, length)| seed_to_location.map_range(*start, *length))
+        .map(|(start, _)| start)
+        .min()
+        .unwrap();
+    println!("Part 2: {}", part2);
 }
@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{error::ErrorKind, CommandFactory, Parser, ValueEnum};
 use itertools::Itertools;
 use std::{
     fs::File,
@@ -12,6 +12,21 @@ struct Args {
     data_file: String,
     #[arg(long)]
     debug: bool,
+    #[arg(long, value_enum, default_value_t = Profile::Linear)]
+    profile: Profile,
+    // Top speed for --profile capped
+    #[arg(long, required_if_eq("profile", "capped"))]
+    max_speed: Option<u128>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum Profile {
+    // Speed equals the hold time
+    Linear,
+    // Speed is the square of the hold time
+    Quadratic,
+    // Speed equals the hold time up to --max-speed
+    Capped,
 }
 
 fn main() {
@@ -24,45 +39,275 @@ fn main() {
         .map(|line| line.expect("Failed to read line"))
         .collect();
 
+    if args.max_speed.is_some() && args.profile != Profile::Capped {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--max-speed only applies to --profile capped",
+            )
+            .exit();
+    }
+
+    let profile: Box<dyn ChargeProfile> = match args.profile {
+        Profile::Linear => Box::new(Linear),
+        Profile::Quadratic => Box::new(Quadratic),
+        Profile::Capped => Box::new(Capped {
+            max_speed: args.max_speed.unwrap(),
+        }),
+    };
+
     let times = lines[0]
         .split_ascii_whitespace()
         .skip(1)
-        .map(|time| time.parse::<u64>().unwrap())
+        .map(|time| time.parse::<u128>().unwrap())
         .collect_vec();
     let distances = lines[1]
         .split_ascii_whitespace()
         .skip(1)
-        .map(|distance| distance.parse::<u64>().unwrap())
+        .map(|distance| distance.parse::<u128>().unwrap())
         .collect_vec();
 
-    let mut race_press_distance = Vec::new();
-    for i in 0..times.len() {
-        let time = times[i];
+    let mut part1 = 1;
+    for (i, (time, distance_to_beat)) in times.iter().zip(distances.iter()).enumerate() {
+        let report = RaceReport::new(profile.as_ref(), *time, *distance_to_beat);
+        println!("{}: {}", i, report);
 
-        let mut press_distance = Vec::new();
-        for t in 0..=time {
-            if args.debug {
-                println!("{}: t={}, d={}", i, t, (time - t) * t);
-            }
-            press_distance.push((t, (time - t) * t));
-        }
+        part1 *= report.ways_to_win();
+    }
+
+    println!("Part 1: {}", part1);
 
-        race_press_distance.push(press_distance);
+    let time = join_digits(&lines[0]);
+    let distance_to_beat = join_digits(&lines[1]);
+    let report = RaceReport::new(profile.as_ref(), time, distance_to_beat);
+    if args.debug {
+        println!("Single race: time={}, {}", time, report);
     }
+    println!("Part 2: {}", report.ways_to_win());
+}
 
-    let mut part1 = 1;
-    for i in 0..times.len() {
-        let distance_to_beat = distances[i];
+// Reads a line like "Time:      7  15   30" as the single number 71530
+fn join_digits(line: &str) -> u128 {
+    line.split_ascii_whitespace()
+        .skip(1)
+        .join("")
+        .parse::<u128>()
+        .expect("Race is too large")
+}
+
+trait ChargeProfile {
+    fn speed(&self, hold: u128) -> u128;
+
+    fn distance(&self, hold: u128, time: u128) -> u128 {
+        self.speed(hold).saturating_mul(time - hold)
+    }
+
+    // Inclusive ranges of hold times that beat the record
+    fn winning_intervals(&self, time: u128, record: u128) -> Vec<(u128, u128)> {
+        find_winning_intervals(self, time, record)
+    }
+
+    fn optimal_hold(&self, time: u128) -> u128 {
+        find_optimal_hold(self, time)
+    }
+}
+
+struct Linear;
+
+impl ChargeProfile for Linear {
+    fn speed(&self, hold: u128) -> u128 {
+        hold
+    }
+
+    fn winning_intervals(&self, time: u128, record: u128) -> Vec<(u128, u128)> {
+        winning_hold_range(time, record).into_iter().collect()
+    }
+
+    fn optimal_hold(&self, time: u128) -> u128 {
+        time / 2
+    }
+}
+
+struct Quadratic;
+
+impl ChargeProfile for Quadratic {
+    fn speed(&self, hold: u128) -> u128 {
+        hold.saturating_mul(hold)
+    }
+}
+
+struct Capped {
+    max_speed: u128,
+}
+
+impl ChargeProfile for Capped {
+    fn speed(&self, hold: u128) -> u128 {
+        hold.min(self.max_speed)
+    }
+}
+
+struct RaceReport {
+    record: u128,
+    intervals: Vec<(u128, u128)>,
+    optimal_hold: u128,
+    best_distance: u128,
+}
+
+impl RaceReport {
+    fn new(profile: &dyn ChargeProfile, time: u128, record: u128) -> Self {
+        let optimal_hold = profile.optimal_hold(time);
+        RaceReport {
+            record,
+            intervals: profile.winning_intervals(time, record),
+            optimal_hold,
+            best_distance: profile.distance(optimal_hold, time),
+        }
+    }
 
-        let ways_beat = race_press_distance[i]
+    fn ways_to_win(&self) -> u128 {
+        self.intervals
             .iter()
-            .filter(|(_, distance)| *distance > distance_to_beat)
-            .count();
+            .map(|(first, last)| last - first + 1)
+            .sum()
+    }
+
+    // How far the best hold beats the record by, or falls short of it, as a sign and a
+    // magnitude so the full u128 range of distances fits
+    fn margin(&self) -> (bool, u128) {
+        (
+            self.best_distance < self.record,
+            self.best_distance.abs_diff(self.record),
+        )
+    }
+}
 
-        println!("{}: Beat {} by {} ways", i, distance_to_beat, ways_beat);
+impl std::fmt::Display for RaceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (short, margin) = self.margin();
+        write!(
+            f,
+            "Beat {} by {} ways, holding {}; best hold {} goes {} (margin {}{})",
+            self.record,
+            self.ways_to_win(),
+            self.intervals
+                .iter()
+                .map(|(first, last)| format!("{}..={}", first, last))
+                .join(", "),
+            self.optimal_hold,
+            self.best_distance,
+            if short { "-" } else { "" },
+            margin
+        )
+    }
+}
+
+// Holding for t wins when t * (time - t) > record, i.e. t lies strictly between the roots of
+// t^2 - time * t + record. Returns the inclusive range of winning hold times.
+fn winning_hold_range(time: u128, record: u128) -> Option<(u128, u128)> {
+    let discriminant = time
+        .checked_mul(time)
+        .expect("Race is too large")
+        .checked_sub(record.checked_mul(4).expect("Record is too large"))?;
 
-        part1 *= ways_beat;
+    // isqrt rounds down, so the estimate can be off by one in either direction. Nudge it onto the
+    // exact first winning hold time.
+    let distance = |hold: u128| Linear.distance(hold, time);
+    let mut first = (time - discriminant.isqrt()) / 2;
+    while first > 0 && distance(first - 1) > record {
+        first -= 1;
+    }
+    while first <= time / 2 && distance(first) <= record {
+        first += 1;
+    }
+    if first > time / 2 {
+        return None;
     }
 
-    println!("Part 1: {}", part1);
+    Some((first, time - first))
+}
+
+// Number of evenly spaced hold times the numeric solvers evaluate before refining
+const SAMPLES: u128 = 4096;
+
+fn sample_holds(time: u128) -> Vec<u128> {
+    if time <= SAMPLES {
+        (0..=time).collect()
+    } else {
+        (0..=SAMPLES)
+            .map(|i| time / SAMPLES * i)
+            .chain([time])
+            .dedup()
+            .collect()
+    }
+}
+
+// Brackets every change between winning and losing among the samples and bisects each down to
+// the exact hold time. Winning stretches narrower than the sample spacing are only found if they
+// contain the optimal hold.
+fn find_winning_intervals<P: ChargeProfile + ?Sized>(
+    profile: &P,
+    time: u128,
+    record: u128,
+) -> Vec<(u128, u128)> {
+    let wins = |hold: u128| profile.distance(hold, time) > record;
+    let holds = sample_holds(time)
+        .into_iter()
+        .chain([profile.optimal_hold(time)])
+        .sorted()
+        .dedup()
+        .collect_vec();
+
+    let mut intervals = Vec::new();
+    let mut interval_start = if wins(holds[0]) { Some(holds[0]) } else { None };
+    for (before, after) in holds.iter().tuple_windows() {
+        let (mut low, mut high) = (*before, *after);
+        if wins(low) == wins(high) {
+            continue;
+        }
+        while high - low > 1 {
+            let middle = low + (high - low) / 2;
+            if wins(middle) == wins(low) {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+
+        match interval_start {
+            Some(start) => {
+                intervals.push((start, low));
+                interval_start = None;
+            }
+            None => interval_start = Some(high),
+        }
+    }
+    if let Some(start) = interval_start {
+        intervals.push((start, time));
+    }
+
+    intervals
+}
+
+// Finds the best sample, then ternary searches between its neighbours. This assumes the
+// distance is unimodal around the best sample.
+fn find_optimal_hold<P: ChargeProfile + ?Sized>(profile: &P, time: u128) -> u128 {
+    let distance = |hold: u128| profile.distance(hold, time);
+    let holds = sample_holds(time);
+    let best = holds
+        .iter()
+        .position_max_by_key(|hold| distance(**hold))
+        .unwrap();
+
+    let mut low = holds[best.saturating_sub(1)];
+    let mut high = holds[(best + 1).min(holds.len() - 1)];
+    while high - low > 2 {
+        let third = (high - low) / 3;
+        if distance(low + third) < distance(high - third) {
+            low += third + 1;
+        } else {
+            high -= third;
+        }
+    }
+
+    (low..=high).max_by_key(|hold| distance(*hold)).unwrap()
 }
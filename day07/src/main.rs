@@ -13,8 +13,289 @@ struct Args {
     data_file: String,
     #[arg(long)]
     debug: bool,
+    // Score with these rule files instead of the two built in Camel Cards variants
     #[arg(long)]
-    wildj: bool,
+    rules: Vec<String>,
+}
+
+const STANDARD_RULES: &str = "
+name: standard
+cards: 23456789TJQKA
+hand_size: 5
+";
+
+const JOKERS_WILD_RULES: &str = "
+name: jokers-wild
+cards: J23456789TQKA
+wild: J
+hand_size: 5
+";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum HandType {
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeOfAKind,
+    FullHouse,
+    FourOfAKind,
+    FiveOfAKind,
+}
+
+impl HandType {
+    // Strongest first, the order rule files list categories in
+    const ALL: [HandType; 7] = [
+        HandType::FiveOfAKind,
+        HandType::FourOfAKind,
+        HandType::FullHouse,
+        HandType::ThreeOfAKind,
+        HandType::TwoPair,
+        HandType::OnePair,
+        HandType::HighCard,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            HandType::HighCard => "high-card",
+            HandType::OnePair => "one-pair",
+            HandType::TwoPair => "two-pair",
+            HandType::ThreeOfAKind => "three-of-a-kind",
+            HandType::FullHouse => "full-house",
+            HandType::FourOfAKind => "four-of-a-kind",
+            HandType::FiveOfAKind => "five-of-a-kind",
+        }
+    }
+
+    fn groups(&self) -> Vec<usize> {
+        match self {
+            HandType::HighCard => vec![],
+            HandType::OnePair => vec![2],
+            HandType::TwoPair => vec![2, 2],
+            HandType::ThreeOfAKind => vec![3],
+            HandType::FullHouse => vec![3, 2],
+            HandType::FourOfAKind => vec![4],
+            HandType::FiveOfAKind => vec![5],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Category {
+    name: String,
+    // Minimum sizes of the largest, second largest, ... groups of matching cards
+    groups: Vec<usize>,
+}
+
+impl Category {
+    // Whether the groups of natural cards, largest first, plus `wilds` wild cards can make this
+    // category. Each needed group takes a different natural group, or none at all, and wilds
+    // make up whatever it's short. Pairing the largest needs with the largest groups leaves the
+    // least to make up, since swapping any two pairings can only cost more wilds.
+    fn matches(&self, group_sizes: &[usize], wilds: usize) -> bool {
+        let short: usize = self
+            .groups
+            .iter()
+            .enumerate()
+            .map(|(index, needed)| needed.saturating_sub(*group_sizes.get(index).unwrap_or(&0)))
+            .sum();
+
+        short <= wilds
+    }
+}
+
+// A rule file is made of `key: value` lines:
+//   name: <label for the output>
+//   cards: <every card, weakest first>
+//   wild: <cards that count as anything> (optional)
+//   hand_size: <cards per hand>
+//   category: <name> <group sizes...> (repeated, strongest first; defaults to the Camel Cards
+//             hand types)
+// Blank lines and lines starting with # are ignored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Rules {
+    name: String,
+    card_order: Vec<char>,
+    wilds: Vec<char>,
+    hand_size: usize,
+    categories: Vec<Category>,
+    // Set when the categories are the default Camel Cards hand types, in HandType::ALL order
+    hand_types: bool,
+}
+
+impl Rules {
+    fn standard() -> Self {
+        Self::parse(STANDARD_RULES).unwrap()
+    }
+
+    fn jokers_wild() -> Self {
+        Self::parse(JOKERS_WILD_RULES).unwrap()
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut name = String::new();
+        let mut card_order = Vec::new();
+        let mut wilds = Vec::new();
+        let mut hand_size = None;
+        let mut categories = Vec::new();
+
+        for line in text.lines().map(|line| line.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or(format!("Expected `key: value`, found {}", line))?;
+            let value = value.trim();
+            match key.trim() {
+                "name" => name = value.to_string(),
+                "cards" => card_order = value.chars().filter(|c| !c.is_whitespace()).collect(),
+                "wild" => wilds = value.chars().filter(|c| !c.is_whitespace()).collect(),
+                "hand_size" => {
+                    hand_size = Some(
+                        value
+                            .parse::<usize>()
+                            .map_err(|_| format!("Invalid hand size {}", value))?,
+                    )
+                }
+                "category" => {
+                    let mut parts = value.split_ascii_whitespace();
+                    let category_name = parts.next().ok_or("Category needs a name")?;
+                    let mut groups = parts
+                        .map(|size| size.parse::<usize>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| format!("Invalid group sizes for {}", category_name))?;
+                    // Matching pairs the largest needs with the largest groups
+                    groups.sort_unstable_by(|a, b| b.cmp(a));
+                    categories.push(Category {
+                        name: category_name.to_string(),
+                        groups,
+                    });
+                }
+                other => return Err(format!("Unknown rule {}", other)),
+            }
+        }
+
+        if card_order.is_empty() {
+            return Err("Rules need a `cards` line".to_string());
+        }
+        if card_order.iter().duplicates().next().is_some() {
+            return Err("Cards must only be listed once".to_string());
+        }
+        if let Some(wild) = wilds.iter().find(|wild| !card_order.contains(wild)) {
+            return Err(format!("Wild card {} is not in the card list", wild));
+        }
+        let hand_types = categories.is_empty();
+        if hand_types {
+            categories = HandType::ALL
+                .iter()
+                .map(|hand_type| Category {
+                    name: hand_type.name().to_string(),
+                    groups: hand_type.groups(),
+                })
+                .collect();
+        }
+        // Without a catch-all every hand needs to match something explicitly
+        if !categories.iter().any(|category| category.groups.is_empty()) {
+            categories.push(Category {
+                name: "nothing".to_string(),
+                groups: Vec::new(),
+            });
+        }
+
+        Ok(Rules {
+            name,
+            card_order,
+            wilds,
+            hand_size: hand_size.ok_or("Rules need a `hand_size` line")?,
+            categories,
+            hand_types,
+        })
+    }
+
+    fn card_value(&self, card: char) -> usize {
+        self.card_order
+            .iter()
+            .position(|c| *c == card)
+            .unwrap_or_else(|| panic!("Unknown card {} for {} rules", card, self.name))
+    }
+
+    fn is_wild(&self, card: char) -> bool {
+        self.wilds.contains(&card)
+    }
+
+    // Index into the categories of the strongest one the cards make
+    fn categorize(&self, cards: &[char]) -> usize {
+        let wilds = cards.iter().filter(|card| self.is_wild(**card)).count();
+        let group_sizes = cards
+            .iter()
+            .filter(|card| !self.is_wild(**card))
+            .counts()
+            .into_values()
+            .sorted()
+            .rev()
+            .collect_vec();
+
+        self.categories
+            .iter()
+            .position(|category| category.matches(&group_sizes, wilds))
+            .unwrap()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Hand<'a> {
+    cards: Vec<char>,
+    rules: &'a Rules,
+    category: usize,
+    hand_type: Option<HandType>,
+    values: Vec<usize>,
+}
+
+impl<'a> Hand<'a> {
+    fn new(cards: &str, rules: &'a Rules) -> Self {
+        let cards = cards.chars().collect_vec();
+        assert_eq!(
+            cards.len(),
+            rules.hand_size,
+            "Hand {} has the wrong number of cards for {} rules",
+            cards.iter().collect::<String>(),
+            rules.name
+        );
+        let category = rules.categorize(&cards);
+        Hand {
+            category,
+            hand_type: rules.hand_types.then(|| HandType::ALL[category]),
+            values: cards.iter().map(|card| rules.card_value(*card)).collect(),
+            cards,
+            rules,
+        }
+    }
+
+    fn category_name(&self) -> &str {
+        &self.rules.categories[self.category].name
+    }
+
+    // Only rules using the default categories have hand types
+    fn hand_type(&self) -> Option<HandType> {
+        self.hand_type
+    }
+}
+
+impl Ord for Hand<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        debug_assert_eq!(self.rules, other.rules);
+        // Categories are listed strongest first
+        other
+            .category
+            .cmp(&self.category)
+            .then_with(|| self.values.cmp(&other.values))
+    }
+}
+
+impl PartialOrd for Hand<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 fn main() {
@@ -31,98 +312,238 @@ fn main() {
         .iter()
         .map(|line| {
             let (hand, bid) = line.split_ascii_whitespace().collect_tuple().unwrap();
-            (
-                hand.chars()
-                    .map(|c| {
-                        if c == 'A' {
-                            14
-                        } else if c == 'K' {
-                            13
-                        } else if c == 'Q' {
-                            12
-                        } else if c == 'J' {
-                            if args.wildj {
-                                1
-                            } else {
-                                11
-                            }
-                        } else if c == 'T' {
-                            10
-                        } else {
-                            c.to_string().parse::<u32>().unwrap()
-                        }
-                    })
-                    .collect_vec(),
-                bid.parse::<u32>().unwrap(),
-            )
+            (hand, bid.parse::<u32>().unwrap())
         })
         .collect_vec();
 
-    let type_hand_bids = hands_bids
+    if args.rules.is_empty() {
+        println!(
+            "Part 1: {}",
+            total_winnings(&hands_bids, &Rules::standard(), args.debug)
+        );
+        println!(
+            "Part 2: {}",
+            total_winnings(&hands_bids, &Rules::jokers_wild(), args.debug)
+        );
+        return;
+    }
+
+    for rules_file in args.rules.iter() {
+        let text = std::fs::read_to_string(rules_file).expect("Failed to open rules file");
+        let mut rules = Rules::parse(&text)
+            .unwrap_or_else(|error| panic!("Invalid rules in {}: {}", rules_file, error));
+        if rules.name.is_empty() {
+            rules.name = rules_file.clone();
+        }
+        println!(
+            "{}: {}",
+            rules.name,
+            total_winnings(&hands_bids, &rules, args.debug)
+        );
+    }
+}
+
+fn total_winnings(hands_bids: &[(&str, u32)], rules: &Rules, debug: bool) -> u32 {
+    hands_bids
         .iter()
-        .map(|(hand, bid)| {
-            let card_count = hand.iter().counts();
-            let wilds = card_count.get(&1).map(|w| *w).unwrap_or(0);
-            let non_wild_counts = card_count
-                .iter()
-                .filter(|(card, _)| ***card != 1)
-                .map(|(_, count)| *count)
-                .counts();
-            if non_wild_counts.keys().max().unwrap_or(&0) + wilds == 5 {
-                // 5 of a kind
-                (6, hand.clone(), bid)
-            } else if non_wild_counts.keys().max().unwrap() + wilds == 4 {
-                // 4 of a kind
-                (5, hand.clone(), bid)
-            } else if (non_wild_counts.contains_key(&3) && non_wild_counts.contains_key(&2))
-                // Only need one wild since it would match with any other single card to make the pair
-                || (wilds >= 1 && non_wild_counts.contains_key(&3))
-                || (wilds >= 1 && *non_wild_counts.get(&2).unwrap_or(&0) == 2)
-                || (wilds >= 2 && non_wild_counts.contains_key(&2))
-            {
-                // Full house
-                (4, hand.clone(), bid)
-            } else if non_wild_counts.keys().max().unwrap() + wilds == 3 {
-                // 3 of a kind
-                (3, hand.clone(), bid)
-                // There is no way for a wild to make a two pair and not any hand better
-            } else if *non_wild_counts.get(&2).unwrap_or(&0) == 2 {
-                // Two pair
-                (2, hand.clone(), bid)
-            } else if non_wild_counts.contains_key(&2) || wilds >= 1 {
-                // One pair
-                (1, hand.clone(), bid)
-            } else {
-                // High card
-                (0, hand.clone(), bid)
-            }
-        })
-        .sorted_by(|a, b| {
-            let mut ordering = a.0.cmp(&b.0);
-            if ordering == Ordering::Equal {
-                for i in 0..5 {
-                    ordering = a.1[i].cmp(&b.1[i]);
-                    if ordering != Ordering::Equal {
-                        return ordering;
-                    }
-                }
+        .map(|(hand, bid)| (Hand::new(hand, rules), *bid))
+        .sorted()
+        .enumerate()
+        .map(|(i, (hand, bid))| {
+            if debug {
+                println!(
+                    "{}: {} {} = {}",
+                    i,
+                    hand.cards.iter().collect::<String>(),
+                    hand.hand_type()
+                        .map_or(hand.category_name().to_string(), |hand_type| {
+                            format!("{:?}", hand_type)
+                        }),
+                    (i as u32 + 1) * bid
+                );
             }
-            return ordering;
+            (i as u32 + 1) * bid
         })
-        .collect_vec();
+        .sum()
+}
 
-    let mut part1 = 0;
-    for i in 0..type_hand_bids.len() {
-        if args.debug {
-            println!(
-                "{}: {:?} = {}",
-                i,
-                type_hand_bids[i].1,
-                (i as u32 + 1) * type_hand_bids[i].2
-            )
-        }
-        part1 += (i as u32 + 1) as u32 * type_hand_bids[i].2;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hand_type(cards: &str, rules: &Rules) -> HandType {
+        Hand::new(cards, rules).hand_type().unwrap()
+    }
+
+    #[test]
+    fn classifies_standard_hands() {
+        assert_eq!(
+            hand_type("AAAAA", &Rules::standard()),
+            HandType::FiveOfAKind
+        );
+        assert_eq!(
+            hand_type("AA8AA", &Rules::standard()),
+            HandType::FourOfAKind
+        );
+        assert_eq!(hand_type("23332", &Rules::standard()), HandType::FullHouse);
+        assert_eq!(
+            hand_type("TTT98", &Rules::standard()),
+            HandType::ThreeOfAKind
+        );
+        assert_eq!(hand_type("23432", &Rules::standard()), HandType::TwoPair);
+        assert_eq!(hand_type("A23A4", &Rules::standard()), HandType::OnePair);
+        assert_eq!(hand_type("23456", &Rules::standard()), HandType::HighCard);
+    }
+
+    #[test]
+    fn jacks_are_not_wild_in_standard_rules() {
+        assert_eq!(
+            hand_type("JJJJJ", &Rules::standard()),
+            HandType::FiveOfAKind
+        );
+        assert_eq!(hand_type("QJJQ2", &Rules::standard()), HandType::TwoPair);
+        assert_eq!(hand_type("KTJJT", &Rules::standard()), HandType::TwoPair);
+        assert_eq!(hand_type("2345J", &Rules::standard()), HandType::HighCard);
+    }
+
+    #[test]
+    fn classifies_hands_with_jokers() {
+        assert_eq!(
+            hand_type("JJJJJ", &Rules::jokers_wild()),
+            HandType::FiveOfAKind
+        );
+        assert_eq!(
+            hand_type("AAJJA", &Rules::jokers_wild()),
+            HandType::FiveOfAKind
+        );
+        assert_eq!(
+            hand_type("QJJQ2", &Rules::jokers_wild()),
+            HandType::FourOfAKind
+        );
+        assert_eq!(
+            hand_type("T55J5", &Rules::jokers_wild()),
+            HandType::FourOfAKind
+        );
+        assert_eq!(
+            hand_type("2233J", &Rules::jokers_wild()),
+            HandType::FullHouse
+        );
+        assert_eq!(
+            hand_type("234JJ", &Rules::jokers_wild()),
+            HandType::ThreeOfAKind
+        );
+        assert_eq!(
+            hand_type("2234J", &Rules::jokers_wild()),
+            HandType::ThreeOfAKind
+        );
+        assert_eq!(hand_type("2345J", &Rules::jokers_wild()), HandType::OnePair);
+        // Jokers are the weakest card when breaking ties
+        assert!(
+            Hand::new("JJJJJ", &Rules::jokers_wild()) < Hand::new("22222", &Rules::jokers_wild())
+        );
+    }
+
+    #[test]
+    fn classifies_jokerless_hands_under_wild_rules() {
+        // A joker always does better than two pair or high card, so these only come without one
+        assert_eq!(hand_type("23432", &Rules::jokers_wild()), HandType::TwoPair);
+        assert_eq!(
+            hand_type("23456", &Rules::jokers_wild()),
+            HandType::HighCard
+        );
     }
 
-    println!("Part 1: {}", part1);
+    #[test]
+    fn ties_break_on_first_differing_card() {
+        assert!(Hand::new("33332", &Rules::standard()) > Hand::new("2AAAA", &Rules::standard()));
+        assert!(Hand::new("77888", &Rules::standard()) > Hand::new("77788", &Rules::standard()));
+        assert!(Hand::new("JKKK2", &Rules::standard()) < Hand::new("QQQQ2", &Rules::standard()));
+        assert!(
+            Hand::new("JKKK2", &Rules::jokers_wild()) < Hand::new("QQQQ2", &Rules::jokers_wild())
+        );
+    }
+
+    #[test]
+    fn parses_rule_files() {
+        let rules = Rules::parse(
+            "
+            # Seven card hands where 2s are wild too
+            name: big
+            cards: 23456789TJQKA
+            wild: 2 J
+            hand_size: 7
+            category: seven-of-a-kind 7
+            category: two-triples 3 3
+            category: four-of-a-kind 4
+            category: high-card
+            ",
+        )
+        .unwrap();
+        assert_eq!(rules.wilds, vec!['2', 'J']);
+        assert_eq!(rules.categories.len(), 4);
+
+        assert_eq!(
+            Hand::new("AAAAAJ2", &rules).category_name(),
+            "seven-of-a-kind"
+        );
+        assert_eq!(Hand::new("AAAKKK3", &rules).category_name(), "two-triples");
+        assert_eq!(
+            Hand::new("AAA2KQ3", &rules).category_name(),
+            "four-of-a-kind"
+        );
+        assert_eq!(Hand::new("AKQT953", &rules).category_name(), "high-card");
+        assert!(Hand::new("AAAKKK3", &rules) > Hand::new("AAA2KQ3", &rules));
+        assert_eq!(Hand::new("AKQT953", &rules).hand_type(), None);
+    }
+
+    #[test]
+    fn splits_wilds_across_groups() {
+        let rules = Rules::parse(
+            "
+            cards: 23456789TJQKA
+            wild: 2 J
+            hand_size: 7
+            category: seven-of-a-kind 7
+            category: two-triples 3 3
+            category: four-of-a-kind 4
+            category: high-card
+            ",
+        )
+        .unwrap();
+
+        // One wild finishes each triple, which beats putting both on the aces
+        assert_eq!(Hand::new("AAAKKJ2", &rules).category_name(), "two-triples");
+        assert!(Hand::new("AAAKKJ2", &rules) > Hand::new("AAAAQ53", &rules));
+        assert_eq!(
+            Hand::new("AAKQJ25", &rules).category_name(),
+            "four-of-a-kind"
+        );
+    }
+
+    #[test]
+    fn sorts_category_group_sizes() {
+        let rules = Rules::parse(
+            "
+            cards: 23456789TJQKA
+            hand_size: 5
+            category: full-house 2 3
+            category: three 3
+            category: nothing
+            ",
+        )
+        .unwrap();
+        assert_eq!(rules.categories[0].groups, vec![3, 2]);
+
+        assert_eq!(Hand::new("22233", &rules).category_name(), "full-house");
+        assert_eq!(Hand::new("22234", &rules).category_name(), "three");
+    }
+
+    #[test]
+    fn rejects_invalid_rule_files() {
+        assert!(Rules::parse("hand_size: 5").is_err());
+        assert!(Rules::parse("cards: 23456789TJQKA").is_err());
+        assert!(Rules::parse("cards: AKQ\nwild: J\nhand_size: 5").is_err());
+        assert!(Rules::parse("cards: AAK\nhand_size: 5").is_err());
+        assert!(Rules::parse("cards: AK\nhand_size: 5\nsuits: 4").is_err());
+    }
 }
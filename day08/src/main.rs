@@ -1,7 +1,7 @@
 use clap::Parser;
 use itertools::Itertools;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::File,
     io::{BufRead, BufReader},
 };
@@ -13,6 +13,12 @@ struct Args {
     data_file: String,
     #[arg(long)]
     debug: bool,
+    // Give up on the simulation fallback for part 2 after this many steps
+    #[arg(long, default_value_t = 1_000_000_000_000)]
+    max_steps: usize,
+    // Write the network as a Graphviz DOT file
+    #[arg(long)]
+    dot: Option<String>,
 }
 
 fn main() {
@@ -25,103 +31,330 @@ fn main() {
         .map(|line| line.expect("Failed to read line"))
         .collect();
 
-    let instructions = lines[0].chars().collect_vec();
-
-    let mut graph = HashMap::new();
+    let network = Network::parse(&lines);
 
-    for line in lines.iter().skip(2) {
-        let (start, dest) = line.split(" = ").collect_tuple().unwrap();
-        let (left, right) = dest
-            .trim_matches('(')
-            .trim_matches(')')
-            .split(", ")
-            .collect_tuple()
-            .unwrap();
+    if let Some(start) = network.id("AAA") {
+        let end = network.id("ZZZ").expect("Network has AAA but no ZZZ");
+        let mut current_node = start;
+        let mut steps = 0;
+        if args.debug {
+            print!("{}", network.names[current_node]);
+        }
+        while current_node != end {
+            current_node = network.step(current_node, steps);
+            steps += 1;
+            if args.debug {
+                print!(" -> {}", network.names[current_node]);
+            }
+        }
 
-        graph.insert(start.to_string(), (left.to_string(), right.to_string()));
+        println!("\nPart 1: {}", steps);
     }
 
-    let mut instruction_index = 0;
-    let mut current_node = "AAA".to_string();
-    let mut steps = 0;
+    // Part 2
+    let ghosts = (0..network.names.len())
+        .filter(|node| network.is_start[*node])
+        .map(|start| GhostCycle::find(start, &network))
+        .collect_vec();
+
     if args.debug {
-        print!("{}", current_node);
+        for ghost in ghosts.iter() {
+            println!(
+                "{}: tail: {}, cycle: {}, Z in tail: {:?}, Z in cycle: {:?}",
+                network.names[ghost.start],
+                ghost.tail,
+                ghost.cycle_length,
+                ghost.tail_hits,
+                ghost.cycle_hits
+            );
+        }
     }
-    while current_node != "ZZZ" {
-        steps += 1;
-        let destinations = graph.get(&current_node).unwrap();
 
-        current_node = if instructions[instruction_index] == 'L' {
-            destinations.0.to_owned()
-        } else {
-            destinations.1.to_owned()
-        };
-        if args.debug {
-            print!(" -> {}", current_node);
-        }
-        instruction_index += 1;
-        instruction_index %= instructions.len();
+    if let Some(path) = &args.dot {
+        std::fs::write(path, network.to_dot(&ghosts)).expect("Failed to write DOT file");
     }
 
-    println!("\nPart 1: {}", steps);
+    match earliest_common_z(&ghosts, args.max_steps) {
+        Some(step) => println!("Part 2: {}", step),
+        None => println!("Part 2: the ghosts never all reach Z together"),
+    }
+}
 
-    // Part 2
-    let mut current_nodes = graph
-        .keys()
-        .filter(|key| key.ends_with("A"))
-        .cloned()
-        .collect_vec();
-    let mut first_z_seen = Vec::new();
-    first_z_seen.resize(current_nodes.len(), -1);
+// The node network with names interned to dense ids so each step is an array lookup
+struct Network {
+    names: Vec<String>,
+    // [left, right] successor of each node
+    edges: Vec<[usize; 2]>,
+    is_start: Vec<bool>,
+    is_end: Vec<bool>,
+    // 0 for L, 1 for R, indexing into edges
+    instructions: Vec<usize>,
+}
 
-    instruction_index = 0;
-    steps = 0;
-    while first_z_seen.iter().any(|z| *z == -1) {
-        steps += 1;
+impl Network {
+    fn parse(lines: &[String]) -> Self {
+        let instructions = lines[0]
+            .chars()
+            .map(|c| if c == 'L' { 0 } else { 1 })
+            .collect_vec();
 
-        current_nodes = current_nodes
+        let definitions = lines
             .iter()
-            .map(|node| {
-                let destinations = graph.get(node).unwrap();
-                if instructions[instruction_index] == 'L' {
-                    destinations.0.to_owned()
-                } else {
-                    destinations.1.to_owned()
-                }
+            .skip(2)
+            .map(|line| {
+                let (start, dest) = line.split(" = ").collect_tuple().unwrap();
+                let (left, right) = dest
+                    .trim_matches('(')
+                    .trim_matches(')')
+                    .split(", ")
+                    .collect_tuple()
+                    .unwrap();
+                (start, left, right)
             })
             .collect_vec();
 
-        if args.debug {
-            println!("Step: {}\n\n{:?}\n\n", steps, current_nodes);
+        let ids: HashMap<&str, usize> = definitions
+            .iter()
+            .enumerate()
+            .map(|(id, (name, _, _))| (*name, id))
+            .collect();
+        let edges = definitions
+            .iter()
+            .map(|(_, left, right)| [ids[left], ids[right]])
+            .collect_vec();
+        let names = definitions
+            .iter()
+            .map(|(name, _, _)| name.to_string())
+            .collect_vec();
+
+        Network {
+            is_start: names.iter().map(|name| name.ends_with('A')).collect(),
+            is_end: names.iter().map(|name| name.ends_with('Z')).collect(),
+            names,
+            edges,
+            instructions,
+        }
+    }
+
+    fn id(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    fn step(&self, node: usize, step: usize) -> usize {
+        self.edges[node][self.instructions[step % self.instructions.len()]]
+    }
+
+    fn to_dot(&self, ghosts: &[GhostCycle]) -> String {
+        const COLORS: [&str; 6] = ["blue", "orange", "purple", "brown", "magenta", "cyan"];
+
+        // (node, direction) -> index of the first ghost whose cycle takes that edge
+        let mut cycle_edges: HashMap<(usize, usize), usize> = HashMap::new();
+        for (index, ghost) in ghosts.iter().enumerate() {
+            for edge in ghost.cycle_edges.iter() {
+                cycle_edges.entry(*edge).or_insert(index);
+            }
+        }
+
+        let mut output = String::from("digraph network {\n");
+        for (node, name) in self.names.iter().enumerate() {
+            let style = if self.is_start[node] {
+                " style=filled fillcolor=green"
+            } else if self.is_end[node] {
+                " style=filled fillcolor=red"
+            } else {
+                ""
+            };
+            output += &format!("    {} [label=\"{}\"{}];\n", node, name, style);
         }
+        for (node, [left, right]) in self.edges.iter().enumerate() {
+            for (direction, (target, label)) in [(left, "L"), (right, "R")].into_iter().enumerate()
+            {
+                let style = match cycle_edges.get(&(node, direction)) {
+                    Some(ghost) => format!(" color={} penwidth=3", COLORS[ghost % COLORS.len()]),
+                    None => String::new(),
+                };
+                output += &format!(
+                    "    {} -> {} [label=\"{}\"{}];\n",
+                    node, target, label, style
+                );
+            }
+        }
+        output += "}\n";
+
+        output
+    }
+}
 
-        for i in 0..current_nodes.len() {
-            if current_nodes[i].ends_with("Z") && first_z_seen[i] == -1 {
-                first_z_seen[i] = steps;
+// The walk of a single ghost over (node, instruction index) states. After `tail` steps it repeats
+// every `cycle_length` steps.
+struct GhostCycle {
+    start: usize,
+    tail: usize,
+    cycle_length: usize,
+    // Steps before the cycle starts that land on a Z node
+    tail_hits: Vec<usize>,
+    // Steps in [tail, tail + cycle_length) that land on a Z node
+    cycle_hits: Vec<usize>,
+    // (node, direction) of every edge taken while in the cycle
+    cycle_edges: HashSet<(usize, usize)>,
+}
+
+impl GhostCycle {
+    fn find(start: usize, network: &Network) -> Self {
+        let instruction_count = network.instructions.len();
+        let mut first_seen = vec![usize::MAX; network.names.len() * instruction_count];
+        let mut path = Vec::new();
+        let mut z_steps = Vec::new();
+        let mut node = start;
+        let mut step = 0;
+        loop {
+            let state = node * instruction_count + step % instruction_count;
+            if first_seen[state] != usize::MAX {
+                let tail = first_seen[state];
+                let (cycle_hits, tail_hits) = z_steps.iter().partition(|z| **z >= tail);
+                return GhostCycle {
+                    start,
+                    tail,
+                    cycle_length: step - tail,
+                    tail_hits,
+                    cycle_hits,
+                    cycle_edges: path
+                        .iter()
+                        .enumerate()
+                        .skip(tail)
+                        .map(|(step, node)| (*node, network.instructions[step % instruction_count]))
+                        .collect(),
+                };
+            }
+            first_seen[state] = step;
+            path.push(node);
+            if network.is_end[node] {
+                z_steps.push(step);
             }
+
+            node = network.step(node, step);
+            step += 1;
         }
+    }
 
-        instruction_index += 1;
-        instruction_index %= instructions.len();
+    fn is_at_z(&self, step: usize) -> bool {
+        if step < self.tail {
+            self.tail_hits.contains(&step)
+        } else {
+            let offset = self.tail + (step - self.tail) % self.cycle_length;
+            self.cycle_hits.contains(&offset)
+        }
     }
+}
 
-    if args.debug {
-        println!("First Z's: {:?}", first_z_seen);
+// Above this many combinations of Z positions we simulate instead of running CRT on each
+const MAX_COMBINATIONS: usize = 100_000;
+
+fn earliest_common_z(ghosts: &[GhostCycle], max_steps: usize) -> Option<usize> {
+    // Until every ghost is in its cycle, hits can only be found by stepping through
+    let longest_tail = ghosts.iter().map(|ghost| ghost.tail).max().unwrap_or(0);
+    if let Some(step) = (1..longest_tail).find(|step| ghosts.iter().all(|g| g.is_at_z(*step))) {
+        return Some(step);
     }
 
-    println!("Part 2: {}", lcm(first_z_seen));
+    let combinations = ghosts.iter().try_fold(1_usize, |total, ghost| {
+        total.checked_mul(ghost.cycle_hits.len())
+    });
+    if combinations.is_some_and(|count| count <= MAX_COMBINATIONS) {
+        let solutions = ghosts
+            .iter()
+            .map(|ghost| {
+                ghost
+                    .cycle_hits
+                    .iter()
+                    .map(|hit| (*hit as i128, ghost.cycle_length as i128))
+                    .collect_vec()
+            })
+            .multi_cartesian_product()
+            .map(solve_congruences)
+            .collect::<Result<Vec<_>, _>>();
+
+        // An overflow in any combination means we can't trust CRT here
+        if let Ok(solutions) = solutions {
+            return solutions
+                .into_iter()
+                .flatten()
+                .map(|(remainder, modulus)| {
+                    let start = longest_tail.max(1) as i128;
+                    let step = if remainder >= start {
+                        remainder
+                    } else {
+                        remainder + (start - remainder + modulus - 1) / modulus * modulus
+                    };
+                    step as usize
+                })
+                .min();
+        }
+    }
+
+    simulate_common_z(ghosts, longest_tail.max(1), max_steps)
+}
+
+// Walks the Z hits of the first ghost and checks whether the others line up
+fn simulate_common_z(ghosts: &[GhostCycle], start: usize, max_steps: usize) -> Option<usize> {
+    let first = ghosts.first()?;
+    let mut cycle_start = start - (start - first.tail) % first.cycle_length;
+    while cycle_start <= max_steps {
+        for hit in first.cycle_hits.iter() {
+            let step = cycle_start + hit - first.tail;
+            if step >= start && step <= max_steps && ghosts.iter().all(|g| g.is_at_z(step)) {
+                return Some(step);
+            }
+        }
+        cycle_start += first.cycle_length;
+    }
+
+    None
+}
+
+fn solve_congruences(congruences: Vec<(i128, i128)>) -> Result<Option<(i128, i128)>, ()> {
+    let mut combined = (0, 1);
+    for congruence in congruences {
+        match crt(combined, congruence)? {
+            Some(next) => combined = next,
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(combined))
 }
 
-pub fn lcm(nums: Vec<i64>) -> i64 {
-    if nums.len() == 1 {
-        return nums[0];
+// Combines x = a1 (mod m1) and x = a2 (mod m2) into a single congruence, if one exists. Moduli
+// don't need to be coprime. Err means the combined modulus overflowed.
+fn crt((a1, m1): (i128, i128), (a2, m2): (i128, i128)) -> Result<Option<(i128, i128)>, ()> {
+    let g = gcd_of_two_numbers(m1, m2);
+    if (a2 - a1) % g != 0 {
+        return Ok(None);
     }
-    let a = nums[0];
-    let b = lcm(nums.iter().skip(1).cloned().collect_vec());
-    a * b / gcd_of_two_numbers(a, b)
+    let modulus = (m1 / g).checked_mul(m2).ok_or(())?;
+    let k = ((a2 - a1) / g)
+        .checked_mul(modular_inverse(m1 / g, m2 / g))
+        .ok_or(())?
+        .rem_euclid(m2 / g);
+    let remainder = m1.checked_mul(k).ok_or(())?.checked_add(a1).ok_or(())?;
+
+    Ok(Some((remainder.rem_euclid(modulus), modulus)))
+}
+
+fn modular_inverse(a: i128, modulus: i128) -> i128 {
+    // Extended Euclid, a and modulus are coprime
+    let (mut old_r, mut r) = (a.rem_euclid(modulus), modulus);
+    let (mut old_s, mut s) = (1_i128, 0_i128);
+    while r != 0 {
+        let quotient = old_r / r;
+        (old_r, r) = (r, old_r - quotient * r);
+        (old_s, s) = (s, old_s - quotient * s);
+    }
+
+    old_s.rem_euclid(modulus)
 }
 
-fn gcd_of_two_numbers(a: i64, b: i64) -> i64 {
+fn gcd_of_two_numbers(a: i128, b: i128) -> i128 {
     if b == 0 {
         return a;
     }
@@ -1,5 +1,6 @@
 use clap::Parser;
 use itertools::Itertools;
+use num::{BigInt, BigRational, One, Signed, Zero};
 use std::{
     fs::File,
     io::{BufRead, BufReader},
@@ -12,6 +13,13 @@ struct Args {
     data_file: String,
     #[arg(long)]
     debug: bool,
+    // Offsets to extrapolate to. Positive offsets count forward from the last entry and negative
+    // ones backward from the first, so 1 is part 1 and -1 is part 2. Accepts forms like 1e6.
+    #[arg(long, allow_negative_numbers = true, value_parser = parse_offset)]
+    at: Vec<BigInt>,
+    // Print each sequence's degree and polynomial
+    #[arg(long)]
+    report: bool,
 }
 
 fn main() {
@@ -28,53 +36,240 @@ fn main() {
         .iter()
         .map(|line| {
             line.split_ascii_whitespace()
-                .map(|entry| entry.parse::<i64>().unwrap())
+                .map(|entry| entry.parse::<BigInt>().unwrap())
                 .collect_vec()
         })
         .collect_vec();
 
-    let new_entries = sequences
+    let fits = sequences
         .iter()
         .map(|sequence| {
-            let mut history = Vec::new();
-            history.push(sequence.clone());
-
-            while !history.last().unwrap().iter().all(|entry| *entry == 0) {
-                history.push(
-                    history
-                        .last()
-                        .unwrap()
-                        .iter()
-                        .tuple_windows()
-                        .map(|(a, b)| b - a)
-                        .collect_vec(),
-                );
+            if args.debug {
+                println!("{:?}", history(sequence));
             }
+            NewtonPolynomial::fit(sequence)
+        })
+        .collect_vec();
+
+    if args.report {
+        for (index, fit) in fits.iter().enumerate() {
+            match fit {
+                Ok(polynomial) => println!(
+                    "Line {}: degree {}, f(x) = {}",
+                    index + 1,
+                    polynomial
+                        .degree()
+                        .map_or("-".to_string(), |degree| degree.to_string()),
+                    format_polynomial(&polynomial.coefficients())
+                ),
+                Err(error) => println!("Line {}: unmodelled, {}", index + 1, error),
+            }
+        }
+    }
+
+    // Sequences that can't be modelled make the sums meaningless, unless this is a report, where
+    // they're left out and the sums are labelled as partial
+    let (polynomials, failures): (Vec<_>, Vec<_>) =
+        fits.into_iter()
+            .enumerate()
+            .partition_map(|(index, fit)| match fit {
+                Ok(polynomial) => itertools::Either::Left(polynomial),
+                Err(error) => itertools::Either::Right((index + 1, error)),
+            });
+    if !failures.is_empty() && !args.report {
+        for (line, error) in failures.iter() {
+            eprintln!("Line {}: {}", line, error);
+        }
+        std::process::exit(1);
+    }
+    let partial = if failures.is_empty() {
+        String::new()
+    } else {
+        format!(
+            " (partial, leaving out line {})",
+            failures.iter().map(|(line, _)| line).join(", ")
+        )
+    };
+
+    let sum_at = |offset: &BigInt| -> BigInt {
+        polynomials
+            .iter()
+            .map(|polynomial| polynomial.at_offset(offset))
+            .sum()
+    };
+
+    println!("Part 1: {}{}", sum_at(&BigInt::one()), partial);
+    println!("Part 2: {}{}", sum_at(&-BigInt::one()), partial);
+
+    for offset in args.at.iter() {
+        println!("At {}: {}{}", offset, sum_at(offset), partial);
+    }
+}
+
+// Reads an integer offset, also allowing scientific notation such as 1e6 or -2.5e3
+fn parse_offset(text: &str) -> Result<BigInt, String> {
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (
+            mantissa,
+            exponent
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid exponent in {}", text))?,
+        ),
+        None => (text, 0),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if fraction.len() > exponent {
+        return Err(format!("{} is not a whole number", text));
+    }
+
+    format!(
+        "{}{}{}",
+        whole,
+        fraction,
+        "0".repeat(exponent - fraction.len())
+    )
+    .parse::<BigInt>()
+    .map_err(|_| format!("Invalid offset {}", text))
+}
+
+// Rows of repeated differences, starting with the sequence itself and ending with the first row
+// that is entirely zero (or the last row if none is)
+fn history(sequence: &[BigInt]) -> Vec<Vec<BigInt>> {
+    let mut history = vec![sequence.to_vec()];
+    while history.last().unwrap().len() > 1
+        && !history.last().unwrap().iter().all(|entry| entry.is_zero())
+    {
+        history.push(
+            history
+                .last()
+                .unwrap()
+                .iter()
+                .tuple_windows()
+                .map(|(a, b)| b - a)
+                .collect_vec(),
+        );
+    }
 
-            let mut history_index = history.len() - 1;
+    history
+}
+
+// The sequence as a polynomial in Newton forward difference form,
+// f(x) = sum of differences[j] * C(x, j), where x is the index into the sequence
+struct NewtonPolynomial {
+    // The first entry of each difference row, up to the last non-zero row
+    differences: Vec<BigInt>,
+    length: usize,
+}
 
-            while history_index > 0 {
-                let upper_row_last = *history[history_index - 1].last().unwrap();
-                let current_row_last = *history[history_index].last().unwrap_or(&0);
-                let upper_row_first = *history[history_index - 1].first().unwrap();
-                let current_row_first = *history[history_index].first().unwrap();
-                history[history_index - 1].push(upper_row_last + current_row_last);
-                history[history_index - 1].insert(0, upper_row_first - current_row_first);
+impl NewtonPolynomial {
+    fn fit(sequence: &[BigInt]) -> Result<Self, String> {
+        let history = history(sequence);
+        if !history.last().unwrap().iter().all(|entry| entry.is_zero()) {
+            return Err(format!(
+                "differences of {} entries never reach zero",
+                sequence.len()
+            ));
+        }
 
-                history_index -= 1;
+        Ok(NewtonPolynomial {
+            differences: history
+                .iter()
+                .take(history.len() - 1)
+                .map(|row| row[0].clone())
+                .collect(),
+            length: sequence.len(),
+        })
+    }
+
+    // None for the zero polynomial
+    fn degree(&self) -> Option<usize> {
+        self.differences.len().checked_sub(1)
+    }
+
+    // Coefficients of x^0, x^1, ... with x the index into the sequence
+    fn coefficients(&self) -> Vec<BigRational> {
+        let mut coefficients = vec![BigRational::zero(); self.differences.len()];
+        // x (x - 1) ... (x - j + 1), lowest power first
+        let mut falling_factorial = vec![BigInt::one()];
+        let mut factorial = BigInt::one();
+        for (j, difference) in self.differences.iter().enumerate() {
+            for (power, coefficient) in falling_factorial.iter().enumerate() {
+                coefficients[power] +=
+                    BigRational::new(difference * coefficient, factorial.clone());
+            }
 
-                if args.debug {
-                    println!("{:?}", history);
-                }
+            // Multiply by (x - j) and fold j + 1 into the factorial for the next term
+            let mut next = vec![BigInt::zero(); falling_factorial.len() + 1];
+            for (power, coefficient) in falling_factorial.iter().enumerate() {
+                next[power + 1] += coefficient;
+                next[power] -= coefficient * j;
             }
+            falling_factorial = next;
+            factorial *= j + 1;
+        }
 
-            (*history[0].first().unwrap(), *history[0].last().unwrap())
+        coefficients
+    }
+
+    fn at(&self, x: &BigInt) -> BigInt {
+        let mut total = BigInt::zero();
+        let mut binomial = BigInt::one();
+        for (j, difference) in self.differences.iter().enumerate() {
+            total += difference * &binomial;
+            // C(x, j + 1) = C(x, j) * (x - j) / (j + 1), which always divides exactly
+            binomial = binomial * (x - j) / (j + 1);
+        }
+
+        total
+    }
+
+    fn at_offset(&self, offset: &BigInt) -> BigInt {
+        if offset.is_negative() {
+            self.at(offset)
+        } else {
+            self.at(&(offset + self.length - 1))
+        }
+    }
+}
+
+fn format_polynomial(coefficients: &[BigRational]) -> String {
+    let terms = coefficients
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, coefficient)| !coefficient.is_zero())
+        .map(|(power, coefficient)| {
+            let magnitude = coefficient.abs();
+            let factor = if magnitude.is_one() && power > 0 {
+                String::new()
+            } else if magnitude.is_integer() || power == 0 {
+                magnitude.to_string()
+            } else {
+                format!("({})", magnitude)
+            };
+            let variable = match power {
+                0 => String::new(),
+                1 => "x".to_string(),
+                _ => format!("x^{}", power),
+            };
+            (coefficient.is_negative(), format!("{}{}", factor, variable))
         })
         .collect_vec();
 
-    let part1: i64 = new_entries.iter().map(|(_, second)| second).sum();
-    let part2: i64 = new_entries.iter().map(|(first, _)| first).sum();
+    if terms.is_empty() {
+        return "0".to_string();
+    }
+
+    let mut output = String::new();
+    for (index, (is_negative, term)) in terms.iter().enumerate() {
+        match (index, is_negative) {
+            (0, true) => output += "-",
+            (0, false) => {}
+            (_, true) => output += " - ",
+            (_, false) => output += " + ",
+        }
+        output += term;
+    }
 
-    println!("Part 1: {}", part1);
-    println!("Part 2: {}", part2);
+    output
 }
@@ -1,7 +1,8 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use itertools::Itertools;
+use rand::{rngs::StdRng, seq::IndexedRandom, RngExt, SeedableRng};
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{HashMap, HashSet},
     fs::File,
     io::{BufRead, BufReader},
 };
@@ -9,13 +10,72 @@ use std::{
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    #[arg(long)]
-    data_file: String,
+    #[arg(long, required_unless_present = "generate")]
+    data_file: Option<String>,
     #[arg(long)]
     debug: bool,
+    #[arg(long, value_enum)]
+    render: Option<RenderMode>,
+    #[arg(long)]
+    render_file: Option<String>,
+    // Tile set file with one glyph per line followed by the directions it connects, e.g. "+ NESW"
+    #[arg(long)]
+    tiles: Option<String>,
+    // Generate a random WIDTHxHEIGHT maze and solve that instead of --data-file
+    #[arg(long, value_parser = parse_size)]
+    generate: Option<(usize, usize)>,
+    #[arg(long)]
+    generate_file: Option<String>,
+    #[arg(long)]
+    seed: Option<u64>,
+    // Chance that a tile off the generated loop holds a random pipe
+    #[arg(long, default_value_t = 0.5, value_parser = parse_probability)]
+    distractors: f64,
+}
+
+const STANDARD_TILES: &str = "\
+| NS
+- EW
+L NE
+J NW
+7 SW
+F SE
+.
+";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum RenderMode {
+    Ansi,
+    Svg,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Direction {
+    North,
+    East,
+    South,
+    West,
 }
 
-#[derive(Copy, Clone)]
+impl Direction {
+    const ALL: [Direction; 4] = [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+    ];
+
+    fn opposite(&self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 struct Pipe {
     north: bool,
     east: bool,
@@ -25,226 +85,598 @@ struct Pipe {
 
 impl Pipe {
     fn new(north: bool, east: bool, south: bool, west: bool) -> Self {
-        return Pipe {
+        Pipe {
             north,
             east,
             south,
             west,
-        };
+        }
     }
-}
 
-fn main() {
-    let args = Args::parse();
+    fn connects(&self, direction: Direction) -> bool {
+        match direction {
+            Direction::North => self.north,
+            Direction::East => self.east,
+            Direction::South => self.south,
+            Direction::West => self.west,
+        }
+    }
 
-    let file = File::open(&args.data_file).expect("Failed to open file");
-    let reader = BufReader::new(file);
-    let lines: Vec<String> = reader
-        .lines()
-        .map(|line| line.expect("Failed to read line"))
-        .collect();
+    fn glyph(&self) -> char {
+        match (self.north, self.east, self.south, self.west) {
+            (false, false, false, false) => '·',
+            (true, false, false, false) => '╵',
+            (false, true, false, false) => '╶',
+            (false, false, true, false) => '╷',
+            (false, false, false, true) => '╴',
+            (true, false, true, false) => '│',
+            (false, true, false, true) => '─',
+            (true, true, false, false) => '└',
+            (true, false, false, true) => '┘',
+            (false, false, true, true) => '┐',
+            (false, true, true, false) => '┌',
+            (true, true, true, false) => '├',
+            (true, false, true, true) => '┤',
+            (false, true, true, true) => '┬',
+            (true, true, false, true) => '┴',
+            (true, true, true, true) => '┼',
+        }
+    }
 
-    // grid[y][x]. Up is negative, down positive
-    let grid = lines
-        .iter()
-        .map(|line| line.chars().collect_vec())
-        .collect_vec();
+    fn from_directions(directions: &[Direction]) -> Self {
+        Pipe::new(
+            directions.contains(&Direction::North),
+            directions.contains(&Direction::East),
+            directions.contains(&Direction::South),
+            directions.contains(&Direction::West),
+        )
+    }
 
-    let mut pipe_directions = HashMap::new();
-    pipe_directions.insert('|', Pipe::new(true, false, true, false));
-    pipe_directions.insert('-', Pipe::new(false, true, false, true));
-    pipe_directions.insert('L', Pipe::new(true, true, false, false));
-    pipe_directions.insert('J', Pipe::new(true, false, false, true));
-    pipe_directions.insert('7', Pipe::new(false, false, true, true));
-    pipe_directions.insert('F', Pipe::new(false, true, true, false));
-    pipe_directions.insert('.', Pipe::new(false, false, false, false));
+    fn with(mut self, direction: Direction) -> Self {
+        match direction {
+            Direction::North => self.north = true,
+            Direction::East => self.east = true,
+            Direction::South => self.south = true,
+            Direction::West => self.west = true,
+        }
+        self
+    }
+}
 
-    let start = get_start(&grid);
+// The glyphs a maze can contain and how each connects. Anything else, apart from S, is ground.
+struct TileSet {
+    pipes: HashMap<char, Pipe>,
+}
+
+impl TileSet {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut pipes = HashMap::new();
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let mut chars = line.chars();
+            let glyph = chars.next().unwrap();
+            if glyph == 'S' {
+                return Err("S is reserved for the start".to_string());
+            }
+            let directions = chars
+                .filter(|c| !c.is_whitespace())
+                .map(|c| match c {
+                    'N' => Ok(Direction::North),
+                    'E' => Ok(Direction::East),
+                    'S' => Ok(Direction::South),
+                    'W' => Ok(Direction::West),
+                    _ => Err(format!("Unknown direction {} for tile {}", c, glyph)),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if pipes
+                .insert(glyph, Pipe::from_directions(&directions))
+                .is_some()
+            {
+                return Err(format!("Tile {} is defined twice", glyph));
+            }
+        }
+
+        Ok(TileSet { pipes })
+    }
 
-    let mut current_positions = Vec::new();
-    if pipe_directions[&grid[start.0 - 1][start.1]].south {
-        current_positions.push((start.0 - 1, start.1, start));
+    fn standard() -> Self {
+        TileSet::parse(STANDARD_TILES).unwrap()
     }
-    if pipe_directions[&grid[start.0 + 1][start.1]].north {
-        current_positions.push((start.0 + 1, start.1, start));
+
+    fn pipe(&self, glyph: char) -> Option<Pipe> {
+        self.pipes.get(&glyph).copied()
     }
-    if pipe_directions[&grid[start.0][start.1 - 1]].east {
-        current_positions.push((start.0, start.1 - 1, start));
+
+    fn glyphs(&self) -> Vec<char> {
+        self.pipes.keys().copied().sorted().collect()
     }
-    if pipe_directions[&grid[start.0][start.1 + 1]].west {
-        current_positions.push((start.0, start.1 + 1, start));
+
+    fn glyph(&self, pipe: Pipe) -> Option<char> {
+        self.glyphs()
+            .into_iter()
+            .find(|glyph| self.pipes[glyph] == pipe)
     }
+}
 
-    let mut paths: Vec<Vec<(usize, usize)>> = Vec::new();
-    paths.resize(current_positions.len(), vec![start]);
+// The loop through S in the order it's walked, along with the connections the loop uses on each
+// of its tiles. A crossing is walked twice so appears twice in the path.
+struct Loop {
+    path: Vec<(usize, usize)>,
+    pipes: HashMap<(usize, usize), Pipe>,
+}
 
-    let mut steps = 0;
-    while !current_positions
-        .iter()
-        .any(|(y, x, _)| *y == start.0 && *x == start.1)
-    {
-        for i in 0..current_positions.len() {
-            paths[i].push((current_positions[i].0, current_positions[i].1));
-        }
-
-        steps += 1;
-        current_positions = current_positions
-            .iter()
-            .map(|(y, x, previous)| {
-                let x = *x;
-                let y = *y;
-                let previous = *previous;
-                let pipe = pipe_directions[&grid[y][x]];
-
-                if pipe.north && previous.0 != y - 1 {
-                    (y - 1, x, (y, x))
-                } else if pipe.south && previous.0 != y + 1 {
-                    (y + 1, x, (y, x))
-                } else if pipe.east && previous.1 != x + 1 {
-                    (y, x + 1, (y, x))
-                } else {
-                    (y, x - 1, (y, x))
-                }
-            })
-            .collect_vec();
+fn main() {
+    let args = Args::parse();
+
+    let tiles = match &args.tiles {
+        Some(file) => {
+            TileSet::parse(&std::fs::read_to_string(file).expect("Failed to read tile set"))
+                .unwrap_or_else(|error| panic!("Bad tile set: {}", error))
+        }
+        None => TileSet::standard(),
+    };
+
+    let mut expected = None;
+    // grid[y][x]. Up is negative, down positive
+    let grid = match args.generate {
+        Some((width, height)) => {
+            let seed = args.seed.unwrap_or_else(rand::random);
+            if args.debug {
+                println!("Seed {}", seed);
+            }
+            let mut rng = StdRng::seed_from_u64(seed);
+            let generated = generate_maze(&tiles, width, height, args.distractors, &mut rng);
+            let text = generated
+                .grid
+                .iter()
+                .map(|row| row.iter().collect::<String>() + "\n")
+                .join("");
+            match &args.generate_file {
+                Some(file) => std::fs::write(file, text).expect("Failed to write maze"),
+                None => print!("{}", text),
+            }
+            expected = Some((generated.loop_length / 2, generated.enclosed));
+            generated.grid
+        }
+        None => {
+            let file = File::open(args.data_file.as_ref().unwrap()).expect("Failed to open file");
+            let reader = BufReader::new(file);
+            reader
+                .lines()
+                .map(|line| line.expect("Failed to read line").chars().collect_vec())
+                .collect_vec()
+        }
+    };
+
+    let start = get_start(&grid);
+    let (start_pipe, maze_loop) = find_loop(&grid, &tiles, start);
+
+    if args.debug {
+        println!("S is {:?}", start_pipe);
     }
 
-    println!("Part 1: {}", (steps / 2) + 1);
+    let part1 = maze_loop.path.len() / 2;
+    println!("Part 1: {}", part1);
 
-    let winning_path_index = current_positions
-        .iter()
-        .enumerate()
-        .find(|(_, position)| position.0 == start.0 && position.1 == start.1)
-        .unwrap()
-        .0;
-    let path = paths[winning_path_index].clone();
+    let inside = find_inside(&grid, &maze_loop.pipes);
 
     if args.debug {
-        for y in 0..grid.len() {
-            for x in 0..grid[y].len() {
-                if path.contains(&(y, x)) {
-                    //print!("{}", grid[y][x]);
+        for (y, row) in grid.iter().enumerate() {
+            for x in 0..row.len() {
+                if maze_loop.pipes.contains_key(&(y, x)) {
                     print!("X");
+                } else if inside.contains(&(y, x)) {
+                    print!("!");
                 } else {
                     print!(".");
                 }
             }
-            println!("");
+            println!();
         }
     }
 
-    // This gives me the information to know which side I care about
-    println!("{:?} -> {:?}", path[0], path[1]);
+    if let Some(mode) = args.render {
+        let maze = Maze {
+            grid: &grid,
+            tiles: &tiles,
+            path: &maze_loop.pipes,
+            inside: &inside,
+        };
+        let rendered = match mode {
+            RenderMode::Ansi => maze.render_ansi(),
+            RenderMode::Svg => maze.render_svg(),
+        };
+        match &args.render_file {
+            Some(file) => std::fs::write(file, rendered).expect("Failed to write render file"),
+            None => print!("{}", rendered),
+        }
+    }
 
-    let mut covered: HashSet<(usize, usize)> = HashSet::new();
-    // Let's cheat and assume right hand direction
-    let mut direction = (
-        path[path.len() - 1].0 as isize - path[0].0 as isize,
-        path[path.len() - 1].1 as isize - path[0].1 as isize,
-    );
+    println!("Part 2: {}", inside.len());
 
-    for i in 1..path.len() {
-        direction = (
-            path[i].0 as isize - path[i - 1].0 as isize,
-            path[i].1 as isize - path[i - 1].1 as isize,
+    if let Some(expected) = expected {
+        assert_eq!(
+            (part1, inside.len()),
+            expected,
+            "Solver disagrees with the generated maze"
         );
+    }
+}
 
-        // Right hand rule
-        if direction.0 == 1 {
-            // Down
-            flood_fill((path[i].0, path[i].1 - 1), &path, &grid, &mut covered);
-            flood_fill(
-                (path[i - 1].0, path[i - 1].1 - 1),
-                &path,
-                &grid,
-                &mut covered,
-            );
-        } else if direction.0 == -1 {
-            // Up
-            flood_fill((path[i].0, path[i].1 + 1), &path, &grid, &mut covered);
-            flood_fill(
-                (path[i - 1].0, path[i - 1].1 + 1),
-                &path,
-                &grid,
-                &mut covered,
-            );
-        } else if direction.1 == 1 {
-            // Right
-            flood_fill((path[i].0 + 1, path[i].1), &path, &grid, &mut covered);
-            flood_fill(
-                (path[i - 1].0 + 1, path[i - 1].1),
-                &path,
-                &grid,
-                &mut covered,
-            );
-        } else if direction.1 == -1 {
-            // Left
-            flood_fill((path[i].0 - 1, path[i].1), &path, &grid, &mut covered);
-            flood_fill(
-                (path[i - 1].0 - 1, path[i - 1].1),
-                &path,
-                &grid,
-                &mut covered,
-            );
+fn parse_size(text: &str) -> Result<(usize, usize), String> {
+    let (width, height) = text
+        .split_once('x')
+        .ok_or_else(|| format!("Expected WIDTHxHEIGHT, got {}", text))?;
+    let width = width.parse::<usize>().map_err(|e| e.to_string())?;
+    let height = height.parse::<usize>().map_err(|e| e.to_string())?;
+    if width < 2 || height < 2 {
+        return Err("A maze needs to be at least 2x2".to_string());
+    }
+
+    Ok((width, height))
+}
+
+fn parse_probability(text: &str) -> Result<f64, String> {
+    let probability = text.parse::<f64>().map_err(|e| e.to_string())?;
+    if !(0.0..=1.0).contains(&probability) {
+        return Err(format!("Expected a probability from 0 to 1, got {}", text));
+    }
+
+    Ok(probability)
+}
+
+fn get_start(grid: &[Vec<char>]) -> (usize, usize) {
+    for (y, row) in grid.iter().enumerate() {
+        if let Some(x) = row.iter().position(|c| *c == 'S') {
+            return (y, x);
         }
     }
 
-    if args.debug {
-        for y in 0..grid.len() {
-            for x in 0..grid[y].len() {
-                if path.contains(&(y, x)) {
-                    // print!("{}", grid[y][x]);
-                    print!("X");
-                } else if covered.contains(&(y, x)) {
-                    print!("!");
-                } else {
-                    print!(".");
+    panic!("Can't find Start");
+}
+
+fn neighbour(
+    grid: &[Vec<char>],
+    (y, x): (usize, usize),
+    direction: Direction,
+) -> Option<(usize, usize)> {
+    let (y, x) = match direction {
+        Direction::North => (y.checked_sub(1)?, x),
+        Direction::East => (y, x + 1),
+        Direction::South => (y + 1, x),
+        Direction::West => (y, x.checked_sub(1)?),
+    };
+    grid.get(y)?.get(x)?;
+
+    Some((y, x))
+}
+
+// Works out which pipe is under S and returns it with the loop through it, starting at S
+fn find_loop(grid: &[Vec<char>], tiles: &TileSet, start: (usize, usize)) -> (Pipe, Loop) {
+    let connected = Direction::ALL
+        .into_iter()
+        .filter(|direction| {
+            neighbour(grid, start, *direction)
+                .and_then(|(y, x)| tiles.pipe(grid[y][x]))
+                .is_some_and(|pipe| pipe.connects(direction.opposite()))
+        })
+        .collect_vec();
+
+    // Usually exactly two neighbours point at S, but stray pipes can too so try every pairing
+    connected
+        .iter()
+        .array_combinations()
+        .find_map(|[first, second]| {
+            let start_pipe = Pipe::from_directions(&[*first, *second]);
+            trace_loop(grid, tiles, start, start_pipe).map(|maze_loop| (start_pipe, maze_loop))
+        })
+        .expect("No loop runs through S")
+}
+
+// Follows the pipes round from S. Where a tile offers more than one way on, such as a crossing or
+// a T-junction entered along its bar, the loop carries straight on; if it can't, there's no loop.
+fn trace_loop(
+    grid: &[Vec<char>],
+    tiles: &TileSet,
+    start: (usize, usize),
+    start_pipe: Pipe,
+) -> Option<Loop> {
+    let mut path = vec![start];
+    let mut pipes = HashMap::new();
+    let mut position = start;
+    let mut came_from: Option<Direction> = None;
+    loop {
+        let pipe = if position == start {
+            start_pipe
+        } else {
+            tiles.pipe(grid[position.0][position.1])?
+        };
+        let exits = Direction::ALL
+            .into_iter()
+            .filter(|direction| pipe.connects(*direction) && Some(*direction) != came_from)
+            .collect_vec();
+        let direction = match (exits.as_slice(), came_from) {
+            ([only], _) => *only,
+            ([first, ..], None) => *first,
+            (_, Some(from)) if exits.contains(&from.opposite()) => from.opposite(),
+            _ => return None,
+        };
+
+        let used = pipes.entry(position).or_insert_with(Pipe::default);
+        *used = used.with(direction);
+
+        position = neighbour(grid, position, direction)?;
+        came_from = Some(direction.opposite());
+        let used = pipes.entry(position).or_insert_with(Pipe::default);
+        *used = used.with(direction.opposite());
+        if position == start {
+            return start_pipe
+                .connects(direction.opposite())
+                .then_some(Loop { path, pipes });
+        }
+        let next_pipe = tiles.pipe(grid[position.0][position.1])?;
+        if !next_pipe.connects(direction.opposite()) {
+            return None;
+        }
+        path.push(position);
+    }
+}
+
+// Scans each row keeping track of whether we're inside the loop. Crossing a loop tile that
+// connects north flips the parity, which counts a straight | once and a bend pair like L7 once
+// while ignoring ones like LJ that only touch the row. Only the connections the loop actually
+// uses count, so a crossing flips once and a T-junction's spare arm is ignored.
+fn find_inside(
+    grid: &[Vec<char>],
+    path: &HashMap<(usize, usize), Pipe>,
+) -> HashSet<(usize, usize)> {
+    let mut inside = HashSet::new();
+    for (y, row) in grid.iter().enumerate() {
+        let mut is_inside = false;
+        for x in 0..row.len() {
+            match path.get(&(y, x)) {
+                Some(pipe) => {
+                    if pipe.north {
+                        is_inside = !is_inside;
+                    }
+                }
+                None => {
+                    if is_inside {
+                        inside.insert((y, x));
+                    }
                 }
             }
-            println!("");
         }
     }
 
-    println!("Part 2: {}", covered.len());
+    inside
 }
 
-fn get_start(grid: &Vec<Vec<char>>) -> (usize, usize) {
-    for y in 0..grid.len() {
-        for x in 0..grid[y].len() {
-            if grid[y][x] == 'S' {
-                return (y, x);
-            }
+struct GeneratedMaze {
+    grid: Vec<Vec<char>>,
+    loop_length: usize,
+    enclosed: usize,
+}
+
+// Grows a random blob of cells on the lattice whose corners are the tile centres, then lays the
+// loop along the blob's outline. Cells are only added if they keep the blob free of holes and of
+// cells touching only at a corner, so the outline is always a single simple loop. Every other tile
+// gets a random pipe with probability `distractors`.
+fn generate_maze(
+    tiles: &TileSet,
+    width: usize,
+    height: usize,
+    distractors: f64,
+    rng: &mut StdRng,
+) -> GeneratedMaze {
+    let (rows, columns) = (height - 1, width - 1);
+    let mut region = vec![vec![false; columns]; rows];
+    let in_region = |region: &[Vec<bool>], y: isize, x: isize| {
+        y >= 0
+            && x >= 0
+            && region
+                .get(y as usize)
+                .and_then(|row| row.get(x as usize))
+                .copied()
+                .unwrap_or(false)
+    };
+
+    let first = (rng.random_range(0..rows), rng.random_range(0..columns));
+    region[first.0][first.1] = true;
+    let mut cells = vec![first];
+    let target = rng.random_range(1..=(rows * columns).div_ceil(2));
+    let mut attempts = 0;
+    while cells.len() < target && attempts < 20 * rows * columns {
+        attempts += 1;
+        let (y, x) = *cells.choose(rng).unwrap();
+        let (y, x) = match Direction::ALL.choose(rng).unwrap() {
+            Direction::North => (y as isize - 1, x as isize),
+            Direction::East => (y as isize, x as isize + 1),
+            Direction::South => (y as isize + 1, x as isize),
+            Direction::West => (y as isize, x as isize - 1),
+        };
+        if y < 0 || x < 0 || y as usize >= rows || x as usize >= columns {
+            continue;
+        }
+        if in_region(&region, y, x) {
+            continue;
+        }
+
+        // The ring of eight neighbours, clockwise from north. Adding the cell is safe when the
+        // blob's cells in the ring form one unbroken run that includes an edge neighbour.
+        let ring = [
+            (-1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+            (1, 0),
+            (1, -1),
+            (0, -1),
+            (-1, -1),
+        ]
+        .map(|(dy, dx)| in_region(&region, y + dy, x + dx));
+        let runs = (0..8).filter(|i| ring[*i] && !ring[(i + 7) % 8]).count();
+        let touches_edge = ring.iter().step_by(2).any(|inside| *inside);
+        if runs == 1 && touches_edge {
+            region[y as usize][x as usize] = true;
+            cells.push((y as usize, x as usize));
         }
     }
 
-    panic!("Can't find Start");
-}
+    let empty = tiles.glyph(Pipe::default()).unwrap_or('.');
+    let glyphs = tiles.glyphs();
+    let mut grid = vec![vec![empty; width]; height];
+    let mut path = Vec::new();
+    let mut enclosed = 0;
+    for (y, row) in grid.iter_mut().enumerate() {
+        for (x, tile) in row.iter_mut().enumerate() {
+            let (y, x) = (y as isize, x as isize);
+            let (north_west, north_east) = (
+                in_region(&region, y - 1, x - 1),
+                in_region(&region, y - 1, x),
+            );
+            let (south_west, south_east) = (in_region(&region, y, x - 1), in_region(&region, y, x));
+            let pipe = Pipe::new(
+                north_west != north_east,
+                north_east != south_east,
+                south_west != south_east,
+                north_west != south_west,
+            );
 
-fn flood_fill(
-    start: (usize, usize),
-    path: &Vec<(usize, usize)>,
-    grid: &Vec<Vec<char>>,
-    checked: &mut HashSet<(usize, usize)>,
-) {
-    let mut to_check = VecDeque::new();
-    to_check.push_back(start);
-
-    while !to_check.is_empty() {
-        let cell = to_check.pop_back().unwrap();
-        if !checked.contains(&cell) && !path.contains(&cell) {
-            checked.insert(cell.clone());
-            if cell.0 < grid.len() - 1 {
-                to_check.push_back((cell.0 + 1, cell.1));
+            if pipe != Pipe::default() {
+                *tile = tiles
+                    .glyph(pipe)
+                    .unwrap_or_else(|| panic!("Tile set has no glyph for {:?}", pipe));
+                path.push((y as usize, x as usize));
+            } else if north_west && north_east && south_west && south_east {
+                enclosed += 1;
             }
-            if cell.0 > 0 {
-                to_check.push_back((cell.0 - 1, cell.1));
+            if pipe == Pipe::default() && rng.random_bool(distractors) {
+                *tile = *glyphs.choose(rng).unwrap();
             }
-            if cell.1 < grid[0].len() - 1 {
-                to_check.push_back((cell.0, cell.1 + 1));
+        }
+    }
+
+    // Stop distractors pointing into S so the loop through it stays the only one
+    let start = *path.choose(rng).unwrap();
+    grid[start.0][start.1] = 'S';
+    for direction in Direction::ALL {
+        if let Some((y, x)) = neighbour(&grid, start, direction) {
+            let is_stray = !path.contains(&(y, x))
+                && tiles
+                    .pipe(grid[y][x])
+                    .is_some_and(|pipe| pipe.connects(direction.opposite()));
+            if is_stray {
+                grid[y][x] = empty;
+            }
+        }
+    }
+
+    GeneratedMaze {
+        grid,
+        loop_length: path.len(),
+        enclosed,
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Tile {
+    Loop,
+    Inside,
+    Outside,
+}
+
+struct Maze<'a> {
+    grid: &'a [Vec<char>],
+    tiles: &'a TileSet,
+    // The connections the loop uses on each of its tiles
+    path: &'a HashMap<(usize, usize), Pipe>,
+    inside: &'a HashSet<(usize, usize)>,
+}
+
+impl Maze<'_> {
+    fn tile(&self, position: (usize, usize)) -> Tile {
+        if self.path.contains_key(&position) {
+            Tile::Loop
+        } else if self.inside.contains(&position) {
+            Tile::Inside
+        } else {
+            Tile::Outside
+        }
+    }
+
+    fn pipe(&self, (y, x): (usize, usize)) -> Pipe {
+        match self.path.get(&(y, x)) {
+            Some(pipe) => *pipe,
+            None => self.tiles.pipe(self.grid[y][x]).unwrap_or_default(),
+        }
+    }
+
+    fn render_ansi(&self) -> String {
+        let mut output = String::new();
+        for (y, row) in self.grid.iter().enumerate() {
+            for x in 0..row.len() {
+                let pipe = self.pipe((y, x));
+                let style = match self.tile((y, x)) {
+                    Tile::Loop => "\x1b[1;97m",
+                    // Pipes off the loop are junk, so dim them on top of the shading
+                    Tile::Inside if pipe != Pipe::default() => "\x1b[2;42m",
+                    Tile::Inside => "\x1b[42m",
+                    Tile::Outside if pipe != Pipe::default() => "\x1b[2;100m",
+                    Tile::Outside => "\x1b[100m",
+                };
+                output += &format!("{}{}\x1b[0m", style, pipe.glyph());
             }
-            if cell.1 > 0 {
-                to_check.push_back((cell.0, cell.1 - 1));
+            output.push('\n');
+        }
+
+        output
+    }
+
+    fn render_svg(&self) -> String {
+        const CELL: usize = 10;
+        let height = self.grid.len();
+        let width = self.grid.iter().map(|row| row.len()).max().unwrap_or(0);
+
+        let mut output = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+            width * CELL,
+            height * CELL
+        );
+        for (y, row) in self.grid.iter().enumerate() {
+            for x in 0..row.len() {
+                let (fill, stroke, stroke_width) = match self.tile((y, x)) {
+                    Tile::Loop => ("white", "black", 3),
+                    Tile::Inside => ("lightgreen", "gray", 1),
+                    Tile::Outside => ("lightgray", "gray", 1),
+                };
+                output += &format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+                    x * CELL,
+                    y * CELL,
+                    CELL,
+                    CELL,
+                    fill
+                );
+
+                // Draw each connection as a line from the centre to the edge of the cell
+                let (centre_x, centre_y) = (x * CELL + CELL / 2, y * CELL + CELL / 2);
+                let pipe = self.pipe((y, x));
+                for direction in Direction::ALL.into_iter().filter(|d| pipe.connects(*d)) {
+                    let (end_x, end_y) = match direction {
+                        Direction::North => (centre_x, y * CELL),
+                        Direction::East => ((x + 1) * CELL, centre_y),
+                        Direction::South => (centre_x, (y + 1) * CELL),
+                        Direction::West => (x * CELL, centre_y),
+                    };
+                    output += &format!(
+                        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\" stroke-linecap=\"square\"/>\n",
+                        centre_x, centre_y, end_x, end_y, stroke, stroke_width
+                    );
+                }
             }
         }
+        output += "</svg>\n";
+
+        output
     }
 }
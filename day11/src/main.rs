@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use itertools::Itertools;
 use std::{
     fs::File,
@@ -12,8 +12,24 @@ struct Args {
     data_file: String,
     #[arg(long)]
     debug: bool,
-    #[arg(long, default_value("2"))]
-    expand_by: usize,
+    // Extra expansion factors to report alongside parts 1 and 2, e.g. --expand-by 10,100
+    #[arg(long, value_delimiter = ',', value_parser = parse_factor)]
+    expand_by: Vec<usize>,
+    #[arg(long, value_enum, default_value_t = Metric::Manhattan)]
+    metric: Metric,
+    // Print each galaxy's nearest neighbour. Reports use the --expand-by factors, or 2 without any.
+    #[arg(long)]
+    nearest: bool,
+    // Print how many pairs are at each distance
+    #[arg(long)]
+    histogram: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum Metric {
+    Manhattan,
+    Chebyshev,
+    Euclidean,
 }
 
 fn main() {
@@ -26,69 +42,224 @@ fn main() {
         .map(|line| line.expect("Failed to read line"))
         .collect();
 
-    let mut galaxy_positions = lines
-        .iter()
-        .enumerate()
-        .flat_map(|(y, line)| {
-            line.chars()
-                .enumerate()
-                .filter(|(_, c)| *c == '#')
-                .map(move |(x, _)| (x, y))
-        })
-        .collect_vec();
+    let universe = Universe::parse(&lines);
 
-    let mut i = galaxy_positions.iter().map(|(x, _)| *x).max().unwrap() as isize - 1;
-    while i >= 0 {
-        if !galaxy_positions.iter().any(|(x, _)| *x == i as usize) {
-            // Expand the column
-            if args.debug {
-                println!("Expanding column {}", i);
+    if args.debug {
+        for (x, before) in universe.empty_columns_before.iter().enumerate() {
+            if universe.empty_columns_before.get(x + 1) > Some(before) {
+                println!("Expanding column {}", x);
             }
-            galaxy_positions
-                .iter_mut()
-                .filter(|(x, _)| *x > i as usize)
-                .for_each(|(x, _)| *x += args.expand_by - 1);
         }
+        for (y, before) in universe.empty_rows_before.iter().enumerate() {
+            if universe.empty_rows_before.get(y + 1) > Some(before) {
+                println!("Expanding row {}", y);
+            }
+        }
+    }
 
-        i -= 1;
+    let metric = args.metric;
+    println!("Part 1: {}", metric.total(&universe.expanded(2)));
+    println!("Part 2: {}", metric.total(&universe.expanded(1_000_000)));
+
+    for factor in args.expand_by.iter() {
+        println!(
+            "Expanded by {}: {}",
+            factor,
+            metric.total(&universe.expanded(*factor))
+        );
     }
 
-    i = galaxy_positions.iter().map(|(_, y)| *y).max().unwrap() as isize - 1;
-    while i >= 0 {
-        if !galaxy_positions.iter().any(|(_, y)| *y == i as usize) {
-            // Expand the row
-            if args.debug {
-                println!("Expanding row {}", i);
+    let report_factors = if args.expand_by.is_empty() {
+        vec![2]
+    } else {
+        args.expand_by.clone()
+    };
+    for factor in report_factors {
+        let galaxies = universe.expanded(factor);
+        if args.nearest {
+            println!("Nearest neighbours, expanded by {}:", factor);
+            for (i, nearest) in nearest_neighbours(metric, &galaxies).iter().enumerate() {
+                if let Some((j, measure)) = nearest {
+                    println!(
+                        "  {} at {:?} -> {} at {:?}: {}",
+                        i + 1,
+                        galaxies[i],
+                        j + 1,
+                        galaxies[*j],
+                        metric.format(*measure)
+                    );
+                }
+            }
+        }
+        if args.histogram {
+            println!("Distances, expanded by {}:", factor);
+            let counts = galaxies
+                .iter()
+                .array_combinations()
+                .map(|[a, b]| metric.measure(*a, *b))
+                .counts();
+            for (measure, count) in counts.into_iter().sorted() {
+                println!("  {}: {}", metric.format(measure), count);
             }
-            galaxy_positions
-                .iter_mut()
-                .filter(|(_, y)| *y > i as usize)
-                .for_each(|(_, y)| *y += args.expand_by - 1);
         }
+    }
+}
 
-        i -= 1;
+// Expansion replaces each empty row or column with `factor` of them, so it has to be at least 1
+fn parse_factor(text: &str) -> Result<usize, String> {
+    match text.parse::<usize>() {
+        Ok(0) => Err("Expansion factor must be at least 1".to_string()),
+        Ok(factor) => Ok(factor),
+        Err(error) => Err(error.to_string()),
     }
+}
 
-    let part1_distances = galaxy_positions
-        .iter()
-        .combinations(2)
-        .map(|galaxies| {
-            let (x1, y1) = galaxies[0];
-            let (x2, y2) = galaxies[1];
+impl Metric {
+    // An exact integer that orders pairs the same way as their distance. That's the distance
+    // itself except for Euclidean, where it's the squared distance.
+    fn measure(&self, (x1, y1): (usize, usize), (x2, y2): (usize, usize)) -> u128 {
+        let (dx, dy) = (x1.abs_diff(x2) as u128, y1.abs_diff(y2) as u128);
+        match self {
+            Metric::Manhattan => dx + dy,
+            Metric::Chebyshev => dx.max(dy),
+            Metric::Euclidean => dx * dx + dy * dy,
+        }
+    }
 
-            let distance = x1.abs_diff(*x2) + y1.abs_diff(*y2);
+    fn format(&self, measure: u128) -> String {
+        match self {
+            Metric::Euclidean => format!("{:.3}", (measure as f64).sqrt()),
+            _ => measure.to_string(),
+        }
+    }
 
-            if args.debug {
-                println!("{}, {} <-> {}, {}: {}", x1, y1, x2, y2, distance);
+    // Sum of the distances between every pair of galaxies
+    fn total(&self, galaxies: &[(usize, usize)]) -> String {
+        let xs = || galaxies.iter().map(|(x, _)| *x as i128);
+        let ys = || galaxies.iter().map(|(_, y)| *y as i128);
+        match self {
+            Metric::Manhattan => (axis_total(xs()) + axis_total(ys())).to_string(),
+            // Rotating by 45 degrees turns Chebyshev distance into half the Manhattan distance
+            Metric::Chebyshev => {
+                let us = xs().zip(ys()).map(|(x, y)| x + y);
+                let vs = xs().zip(ys()).map(|(x, y)| x - y);
+                ((axis_total(us) + axis_total(vs)) / 2).to_string()
             }
+            // No shortcut here, so this one stays quadratic
+            Metric::Euclidean => format!(
+                "{:.3}",
+                galaxies
+                    .iter()
+                    .array_combinations()
+                    .map(|[a, b]| (self.measure(*a, *b) as f64).sqrt())
+                    .sum::<f64>()
+            ),
+        }
+    }
+}
+
+// Sum of |a - b| over every pair of coordinates. Once sorted, each coordinate is the larger one in
+// a pair with every coordinate before it.
+fn axis_total(coordinates: impl Iterator<Item = i128>) -> i128 {
+    let mut total = 0;
+    let mut sum_before = 0;
+    for (i, coordinate) in coordinates.sorted_unstable().enumerate() {
+        total += coordinate * i as i128 - sum_before;
+        sum_before += coordinate;
+    }
+
+    total
+}
 
-            distance
-        })
+// For each galaxy, the closest other galaxy (lowest index on ties) and the measure to it. Sweeps
+// outwards in x order, stopping once the x gap alone is further than the best so far.
+fn nearest_neighbours(metric: Metric, galaxies: &[(usize, usize)]) -> Vec<Option<(usize, u128)>> {
+    let order = (0..galaxies.len())
+        .sorted_by_key(|i| galaxies[*i].0)
         .collect_vec();
 
-    if args.debug {
-        println!("{:?}", part1_distances);
+    let mut nearest = vec![None; galaxies.len()];
+    for (rank, i) in order.iter().enumerate() {
+        let galaxy = galaxies[*i];
+        let mut best: Option<(u128, usize)> = None;
+        let mut consider = |j: usize| {
+            let gap = metric.measure((galaxy.0, 0), (galaxies[j].0, 0));
+            if best.is_some_and(|(measure, _)| gap > measure) {
+                return false;
+            }
+            let candidate = (metric.measure(galaxy, galaxies[j]), j);
+            if best.is_none_or(|best| candidate < best) {
+                best = Some(candidate);
+            }
+            true
+        };
+        for j in order[rank + 1..].iter() {
+            if !consider(*j) {
+                break;
+            }
+        }
+        for j in order[..rank].iter().rev() {
+            if !consider(*j) {
+                break;
+            }
+        }
+        nearest[*i] = best.map(|(measure, j)| (j, measure));
     }
 
-    println!("Part 1: {}", part1_distances.iter().cloned().sum::<usize>());
+    nearest
+}
+
+struct Universe {
+    galaxies: Vec<(usize, usize)>,
+    // Number of empty columns (rows) strictly before each x (y), one past the last galaxy
+    empty_columns_before: Vec<usize>,
+    empty_rows_before: Vec<usize>,
+}
+
+impl Universe {
+    fn parse(lines: &[String]) -> Self {
+        let galaxies = lines
+            .iter()
+            .enumerate()
+            .flat_map(|(y, line)| {
+                line.chars()
+                    .enumerate()
+                    .filter(|(_, c)| *c == '#')
+                    .map(move |(x, _)| (x, y))
+            })
+            .collect_vec();
+
+        let empty_before = |coordinates: Vec<usize>| -> Vec<usize> {
+            let size = coordinates.iter().max().map_or(0, |max| max + 1);
+            let mut occupied = vec![false; size];
+            for coordinate in coordinates {
+                occupied[coordinate] = true;
+            }
+
+            let mut before = vec![0];
+            for is_occupied in occupied {
+                before.push(before.last().unwrap() + usize::from(!is_occupied));
+            }
+            before
+        };
+
+        Universe {
+            empty_columns_before: empty_before(galaxies.iter().map(|(x, _)| *x).collect()),
+            empty_rows_before: empty_before(galaxies.iter().map(|(_, y)| *y).collect()),
+            galaxies,
+        }
+    }
+
+    // Galaxy positions once every empty row and column has grown to `factor` times its size
+    fn expanded(&self, factor: usize) -> Vec<(usize, usize)> {
+        self.galaxies
+            .iter()
+            .map(|(x, y)| {
+                (
+                    x + self.empty_columns_before[*x] * (factor - 1),
+                    y + self.empty_rows_before[*y] * (factor - 1),
+                )
+            })
+            .collect()
+    }
 }
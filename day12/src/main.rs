@@ -1,7 +1,7 @@
 use clap::Parser;
 use itertools::Itertools;
+use rand::{rngs::StdRng, RngExt, SeedableRng};
 use std::{
-    collections::HashMap,
     fs::File,
     io::{BufRead, BufReader},
 };
@@ -9,164 +9,454 @@ use std::{
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    #[arg(long)]
-    data_file: String,
+    #[arg(long, required_unless_present = "rows")]
+    data_file: Option<String>,
     #[arg(long)]
     debug: bool,
+    // How many copies of each record part 2 joins together
+    #[arg(long, default_value_t = 5)]
+    unfold: usize,
+    // Only report on this line (counting from 1) rather than every line
+    #[arg(long)]
+    line: Option<usize>,
+    // Unfold factor for the reports below
+    #[arg(long, default_value_t = 1)]
+    report_unfold: usize,
+    // List arrangements a page at a time
+    #[arg(long)]
+    list: bool,
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    page: u64,
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u64).range(1..))]
+    page_size: u64,
+    // Draw this many arrangements uniformly at random
+    #[arg(long)]
+    sample: Option<usize>,
+    #[arg(long)]
+    seed: Option<u64>,
+    // Show which ? are the same in every arrangement
+    #[arg(long)]
+    forced: bool,
+    // Solve a nonogram instead, with one line of comma separated clues per row and per column
+    #[arg(long, requires = "columns")]
+    rows: Option<String>,
+    #[arg(long, requires = "rows")]
+    columns: Option<String>,
 }
 
 fn main() {
     let args = Args::parse();
 
-    let file = File::open(&args.data_file).expect("Failed to open file");
+    if let (Some(rows), Some(columns)) = (&args.rows, &args.columns) {
+        let nonogram = Nonogram {
+            rows: read_clues(rows),
+            columns: read_clues(columns),
+        };
+        let grid = vec![vec!['?'; nonogram.columns.len()]; nonogram.rows.len()];
+        let mut solutions = Vec::new();
+        nonogram.solve(grid, &mut solutions, args.debug);
+        match solutions.len() {
+            0 => println!("No solution"),
+            1 => println!("Unique solution"),
+            _ => println!("Multiple solutions, showing two"),
+        }
+        for solution in solutions.iter() {
+            print!("{}", nonogram.render(solution));
+            println!();
+        }
+        return;
+    }
+
+    let file = File::open(args.data_file.as_ref().unwrap()).expect("Failed to open file");
     let reader = BufReader::new(file);
     let lines: Vec<String> = reader
         .lines()
         .map(|line| line.expect("Failed to read line"))
         .collect();
 
-    let mut memoization = HashMap::new();
-    let arrangement_counts = lines
-        .iter()
-        .map(|line| {
-            let (springs, groups) = line.split_ascii_whitespace().collect_tuple().unwrap();
-            let springs = springs.chars().collect_vec();
-            let groups = groups
-                .split(",")
-                .map(|group| group.parse::<i32>().unwrap())
-                .collect_vec();
+    let records = lines.iter().map(|line| Record::parse(line)).collect_vec();
 
-            let count = valid_count(springs, groups, &mut memoization, args.debug);
+    let total = |unfold: usize| -> u64 {
+        records
+            .iter()
+            .zip(lines.iter())
+            .map(|(record, line)| {
+                let record = record.unfold(unfold);
+                let count = Arrangements::<u64>::new(&record).valid_count(0, 0);
 
-            if args.debug {
-                println!("{} -> {}", line, count);
-            }
+                if args.debug {
+                    println!("{} x{} -> {}", line, unfold, count);
+                }
 
-            return count;
-        })
-        .collect_vec();
+                count
+            })
+            .sum()
+    };
 
-    println!("Part 1: {}", arrangement_counts.iter().sum::<u64>());
+    println!("Part 1: {}", total(1));
+    println!("Part 2: {}", total(args.unfold));
 
-    // Part 2
-    let arrangement_counts = lines
-        .iter()
-        .map(|line| {
-            let (springs, groups) = line.split_ascii_whitespace().collect_tuple().unwrap();
-            let mut springs = springs.chars().collect_vec();
-            let groups = groups
-                .split(",")
-                .map(|group| group.parse::<i32>().unwrap())
-                .collect_vec();
+    if !args.list && args.sample.is_none() && !args.forced {
+        return;
+    }
 
-            springs.push('?');
-            let mut springs = springs.repeat(5);
-            springs.pop();
-            let groups = groups.repeat(5);
+    let seed = args.seed.unwrap_or_else(rand::random);
+    if args.debug && args.sample.is_some() {
+        println!("Seed {}", seed);
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
 
-            let count = valid_count(springs, groups, &mut memoization, args.debug);
+    for (index, record) in records.iter().enumerate() {
+        if args.line.is_some_and(|line| line != index + 1) {
+            continue;
+        }
+        let record = record.unfold(args.report_unfold);
+        let mut arrangements = Arrangements::<u64>::new(&record);
+        let count = arrangements.valid_count(0, 0);
+        println!(
+            "Line {}: {} with {:?}, {} arrangements",
+            index + 1,
+            record.springs.iter().collect::<String>(),
+            record.groups,
+            count
+        );
 
-            if args.debug {
-                println!("{} -> {}", line, count);
+        if args.list && count > 0 {
+            let first = (args.page - 1) * args.page_size;
+            println!("  Page {} of {}", args.page, count.div_ceil(args.page_size));
+            for rank in first..count.min(first + args.page_size) {
+                println!(
+                    "  {}: {}",
+                    rank + 1,
+                    arrangements.arrangement(rank).iter().collect::<String>()
+                );
             }
+        }
 
-            return count;
-        })
-        .collect_vec();
+        if let Some(samples) = args.sample.filter(|_| count > 0) {
+            for _ in 0..samples {
+                let rank = rng.random_range(0..count);
+                println!(
+                    "  Sample: {}",
+                    arrangements.arrangement(rank).iter().collect::<String>()
+                );
+            }
+        }
 
-    println!("Part 2: {}", arrangement_counts.iter().sum::<u64>());
+        if args.forced && count > 0 {
+            let forced = record.forced();
+            println!(
+                "  Forced: {} ({} of {} unknowns)",
+                forced.iter().collect::<String>(),
+                record
+                    .springs
+                    .iter()
+                    .zip(forced.iter())
+                    .filter(|(spring, forced)| **spring == '?' && **forced != '?')
+                    .count(),
+                record
+                    .springs
+                    .iter()
+                    .filter(|spring| **spring == '?')
+                    .count()
+            );
+        }
+    }
 }
 
-fn valid_count(
+struct Record {
     springs: Vec<char>,
-    groups: Vec<i32>,
-    memoization: &mut HashMap<(Vec<char>, Vec<i32>), u64>,
-    debug: bool,
-) -> u64 {
-    if debug {
-        println!("Processing {:?}, {:?}", springs, groups);
+    groups: Vec<usize>,
+}
+
+impl Record {
+    fn parse(line: &str) -> Self {
+        let (springs, groups) = line.split_ascii_whitespace().collect_tuple().unwrap();
+        Record {
+            springs: springs.chars().collect(),
+            groups: groups
+                .split(",")
+                .map(|group| group.parse::<usize>().unwrap())
+                .collect(),
+        }
     }
 
-    let memoization_key = (springs.clone(), groups.clone());
+    // `factor` copies of the springs joined by '?', and `factor` copies of the groups
+    fn unfold(&self, factor: usize) -> Self {
+        Record {
+            springs: vec![self.springs.clone(); factor].join(&'?'),
+            groups: self.groups.repeat(factor),
+        }
+    }
+
+    // The springs with each ? that takes the same value in every arrangement filled in. A ? is
+    // forced to one value when the record with the other value in its place has no arrangements.
+    // This only needs to know whether arrangements exist, so it works on records with too many
+    // to count.
+    fn forced(&self) -> Vec<char> {
+        let mut forced = self.springs.clone();
+        let mut trial = Record {
+            springs: self.springs.clone(),
+            groups: self.groups.clone(),
+        };
+        for (position, spring) in self.springs.iter().enumerate() {
+            if *spring != '?' {
+                continue;
+            }
+            for (value, other) in [('#', '.'), ('.', '#')] {
+                trial.springs[position] = other;
+                if !Arrangements::<bool>::new(&trial).valid_count(0, 0) {
+                    forced[position] = value;
+                }
+            }
+            trial.springs[position] = '?';
+        }
 
-    if memoization.contains_key(&memoization_key) {
-        return *memoization.get(&memoization_key).unwrap();
+        forced
     }
+}
 
-    let mut return_value = 0;
+// What the arrangement recursion adds up: u64 counts them exactly, panicking if there are too
+// many, while bool only tracks whether there are any, so it can't overflow
+trait Count: Copy {
+    fn from_bool(matched: bool) -> Self;
+    fn add(self, other: Self) -> Self;
+}
 
-    if springs.len() == 0 {
-        if groups.len() == 0 {
-            if debug {
-                println!("+1");
-            }
-            return_value = 1;
-        } else {
-            return_value = 0;
-        }
-    } else if springs[0] == '.' {
-        return_value = valid_count(springs.split_at(1).1.to_vec(), groups, memoization, debug);
-    } else if springs[0] == '#' {
-        return_value = valid_count_group(springs, groups, memoization, debug);
-    } else if springs[0] == '?' {
-        return_value = valid_count(
-            springs.split_at(1).1.to_vec(),
-            groups.clone(),
-            memoization,
-            debug,
-        ) + valid_count_group(springs, groups, memoization, debug);
-    }
-
-    memoization.insert(memoization_key, return_value);
-    return return_value;
+impl Count for u64 {
+    fn from_bool(matched: bool) -> Self {
+        u64::from(matched)
+    }
+
+    fn add(self, other: Self) -> Self {
+        self.checked_add(other)
+            .expect("Arrangement count overflows u64")
+    }
 }
 
-fn valid_count_group(
-    springs: Vec<char>,
-    groups: Vec<i32>,
-    memoization: &mut HashMap<(Vec<char>, Vec<i32>), u64>,
-    debug: bool,
-) -> u64 {
-    if debug {
-        println!("Processing group {:?}, {:?}", springs, groups);
+impl Count for bool {
+    fn from_bool(matched: bool) -> Self {
+        matched
     }
-    if groups.len() == 0 {
-        return 0;
+
+    fn add(self, other: Self) -> Self {
+        self || other
     }
-    let group = groups[0] as usize;
-    if springs.len() < group {
-        return 0;
+}
+
+// Counts the ways to fill in one record. valid_count(position, group) is the number of
+// arrangements of springs[position..] that match groups[group..], memoised in a table for just
+// this record.
+struct Arrangements<'a, C: Count> {
+    springs: &'a [char],
+    groups: &'a [usize],
+    memoization: Vec<Option<C>>,
+}
+
+impl<'a, C: Count> Arrangements<'a, C> {
+    fn new(record: &'a Record) -> Self {
+        Arrangements {
+            springs: &record.springs,
+            groups: &record.groups,
+            memoization: vec![None; (record.springs.len() + 1) * (record.groups.len() + 1)],
+        }
+    }
+
+    fn valid_count(&mut self, position: usize, group: usize) -> C {
+        if position == self.springs.len() {
+            return C::from_bool(group == self.groups.len());
+        }
+
+        let key = position * (self.groups.len() + 1) + group;
+        if let Some(count) = self.memoization[key] {
+            return count;
+        }
+
+        let count = match self.springs[position] {
+            '.' => self.valid_count(position + 1, group),
+            '#' => self.valid_count_group(position, group),
+            '?' => self
+                .valid_count(position + 1, group)
+                .add(self.valid_count_group(position, group)),
+            c => panic!("Unknown spring {}", c),
+        };
+
+        self.memoization[key] = Some(count);
+        count
+    }
+
+    // Arrangements where the next group starts at `position`
+    fn valid_count_group(&mut self, position: usize, group: usize) -> C {
+        // An empty group can't be told apart from the gap around it, so it never matches
+        let Some(&size) = self.groups.get(group).filter(|&&size| size > 0) else {
+            return C::from_bool(false);
+        };
+        let end = position + size;
+        if end > self.springs.len() {
+            return C::from_bool(false);
+        }
+
+        if self.springs[position + 1..end].contains(&'.') {
+            return C::from_bool(false);
+        }
+
+        if end == self.springs.len() {
+            return C::from_bool(group + 1 == self.groups.len());
+        }
+
+        if self.springs[end] == '#' {
+            return C::from_bool(false);
+        }
+
+        self.valid_count(end + 1, group + 1)
+    }
+}
+
+impl Arrangements<'_, u64> {
+    // The arrangement at `rank` counting from 0, ordering arrangements by where each one first
+    // differs, with '.' before '#'. The memoised counts say how many arrangements start each way,
+    // so this walks straight to it without listing the ones before.
+    fn arrangement(&mut self, mut rank: u64) -> Vec<char> {
+        let mut springs = Vec::with_capacity(self.springs.len());
+        let (mut position, mut group) = (0, 0);
+        while position < self.springs.len() {
+            if self.springs[position] != '#' {
+                let operational = self.valid_count(position + 1, group);
+                if rank < operational {
+                    springs.push('.');
+                    position += 1;
+                    continue;
+                }
+                rank -= operational;
+            }
+
+            let size = self.groups[group];
+            springs.extend(std::iter::repeat_n('#', size));
+            position += size;
+            if position < self.springs.len() {
+                springs.push('.');
+                position += 1;
+            }
+            group += 1;
+        }
+
+        springs
     }
+}
 
-    for i in 1..group {
-        if springs[i] == '.' {
-            return 0;
+// One clue list per line. A blank line or 0 is a line with nothing filled in.
+fn read_clues(file: &str) -> Vec<Vec<usize>> {
+    std::fs::read_to_string(file)
+        .expect("Failed to read clues")
+        .lines()
+        .map(|line| {
+            line.split([',', ' '])
+                .filter(|clue| !clue.is_empty())
+                .map(|clue| clue.parse::<usize>().expect("Bad clue"))
+                .filter(|clue| *clue > 0)
+                .collect()
+        })
+        .collect()
+}
+
+// Each row and column of a nonogram is a condition record whose groups are its clues
+struct Nonogram {
+    rows: Vec<Vec<usize>>,
+    columns: Vec<Vec<usize>>,
+}
+
+impl Nonogram {
+    // Fills in every cell one of its lines forces, over and over until nothing changes. Returns
+    // false if some line can no longer be completed.
+    fn propagate(&self, grid: &mut [Vec<char>]) -> bool {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (y, groups) in self.rows.iter().enumerate() {
+                let line = (0..grid[y].len()).map(|x| (y, x)).collect_vec();
+                match Self::propagate_line(grid, &line, groups) {
+                    Some(line_changed) => changed |= line_changed,
+                    None => return false,
+                }
+            }
+            for (x, groups) in self.columns.iter().enumerate() {
+                let line = (0..grid.len()).map(|y| (y, x)).collect_vec();
+                match Self::propagate_line(grid, &line, groups) {
+                    Some(line_changed) => changed |= line_changed,
+                    None => return false,
+                }
+            }
         }
+
+        true
     }
 
-    if springs.len() == group {
-        if groups.len() == 1 {
-            if debug {
-                println!("+1");
+    fn propagate_line(
+        grid: &mut [Vec<char>],
+        line: &[(usize, usize)],
+        groups: &[usize],
+    ) -> Option<bool> {
+        let record = Record {
+            springs: line.iter().map(|(y, x)| grid[*y][*x]).collect(),
+            groups: groups.to_vec(),
+        };
+        if !Arrangements::<bool>::new(&record).valid_count(0, 0) {
+            return None;
+        }
+        if record.springs.iter().all(|cell| *cell != '?') {
+            return Some(false);
+        }
+
+        let mut changed = false;
+        for ((y, x), cell) in line.iter().zip(record.forced()) {
+            if grid[*y][*x] != cell {
+                grid[*y][*x] = cell;
+                changed = true;
             }
-            return 1;
         }
 
-        return 0;
+        Some(changed)
     }
 
-    if springs.len() > group {
-        if springs[group] == '#' {
-            return 0;
+    // Propagates, then guesses the first unknown cell both ways when that stalls. Stops once
+    // two solutions turn up, since that's enough to know it isn't unique.
+    fn solve(&self, mut grid: Vec<Vec<char>>, solutions: &mut Vec<Vec<Vec<char>>>, debug: bool) {
+        if solutions.len() >= 2 || !self.propagate(&mut grid) {
+            return;
         }
 
-        return valid_count(
-            springs.split_at(group + 1).1.to_vec(),
-            groups.split_at(1).1.to_vec(),
-            memoization,
-            debug,
-        );
+        let unknown = grid
+            .iter()
+            .enumerate()
+            .find_map(|(y, row)| row.iter().position(|cell| *cell == '?').map(|x| (y, x)));
+        let Some((y, x)) = unknown else {
+            solutions.push(grid);
+            return;
+        };
+
+        if debug {
+            println!("Guessing at {}, {}", x, y);
+        }
+        for guess in ['#', '.'] {
+            let mut guessed = grid.clone();
+            guessed[y][x] = guess;
+            self.solve(guessed, solutions, debug);
+        }
     }
 
-    return 0;
+    fn render(&self, grid: &[Vec<char>]) -> String {
+        let mut output = String::new();
+        for (row, groups) in grid.iter().zip(self.rows.iter()) {
+            for cell in row {
+                output += match cell {
+                    '#' => "██",
+                    '.' => "  ",
+                    _ => "??",
+                };
+            }
+            output += &format!(" {}\n", groups.iter().join(" "));
+        }
+
+        output
+    }
 }
@@ -12,6 +12,12 @@ struct Args {
     data_file: String,
     #[arg(long)]
     debug: bool,
+    // Also score the patterns allowing exactly this many smudges
+    #[arg(long)]
+    smudges: Option<usize>,
+    // List every axis of every pattern with the cells that differ across it
+    #[arg(long)]
+    report: bool,
 }
 
 fn main() {
@@ -35,106 +41,234 @@ fn main() {
         }
     }
 
-    let part1 = grids
-        .iter()
-        .map(|grid| score_reflection(grid, false, args.debug))
-        .sum::<usize>();
-
-    println!("Part 1: {}", part1);
-
-    let part2 = grids
-        .iter()
-        .map(|grid| score_reflection(grid, true, args.debug))
-        .sum::<usize>();
-
-    println!("Part 2: {}", part2);
-}
-
-fn score_reflection(grid: &Vec<Vec<char>>, with_smudge: bool, debug: bool) -> usize {
-    if debug {
-        for y in 0..grid.len() {
-            for x in 0..grid[0].len() {
-                print!("{}", grid[y][x]);
+    if args.debug {
+        for grid in grids.iter() {
+            for row in grid {
+                println!("{}", row.iter().collect::<String>());
             }
             println!();
         }
     }
-    // First horizontal since the data is already y, x
-    for y in 1..grid.len() {
-        let mut bottom_index = if y > grid.len() / 2 {
-            grid.len() - 1
-        } else {
-            (y * 2) - 1
-        };
-        let mut top_index = if y <= grid.len() / 2 {
-            0
-        } else {
-            y - (grid.len() - y)
+
+    // Part 1, part 2 and then --smudges, all scored from a single search of each pattern
+    let budgets = [0, 1].into_iter().chain(args.smudges).collect_vec();
+    let mut totals = vec![0; budgets.len()];
+    for (index, grid) in grids.iter().enumerate() {
+        let pattern = match Pattern::parse(grid) {
+            Ok(pattern) => pattern,
+            Err(error) => {
+                println!("Pattern {}: {}", index + 1, error);
+                continue;
+            }
         };
+        let reflections = pattern.reflections();
 
-        if debug {
-            println!(
-                "Testing y = {} with top = {}, bottom = {}",
-                y, top_index, bottom_index
-            );
+        if args.report {
+            println!("Pattern {}:", index + 1);
+            for reflection in reflections.iter() {
+                let cells = pattern.mismatched_cells(reflection);
+                if cells.is_empty() {
+                    println!("  {}", reflection);
+                } else {
+                    println!(
+                        "  {}: {}",
+                        reflection,
+                        cells
+                            .iter()
+                            .map(|[(x1, y1), (x2, y2)]| format!("{},{} or {},{}", x1, y1, x2, y2))
+                            .join("; ")
+                    );
+                }
+            }
         }
 
-        let mut mismatch_count = 0;
-        while top_index < bottom_index {
-            for x in 0..grid[0].len() {
-                if grid[top_index][x] != grid[bottom_index][x] {
-                    mismatch_count += 1;
+        for (total, smudges) in totals.iter_mut().zip(budgets.iter()) {
+            match find_reflection(&reflections, *smudges) {
+                Ok(reflection) => {
+                    if args.debug {
+                        println!("Pattern {}: {}", index + 1, reflection);
+                    }
+                    *total += reflection.score();
                 }
+                Err(error) => println!("Pattern {}: {}", index + 1, error),
             }
-            top_index += 1;
-            bottom_index -= 1;
         }
+    }
 
-        if (with_smudge && mismatch_count == 1) || (!with_smudge && mismatch_count == 0) {
-            if debug {
-                println!("y == {}", y);
-            }
-            return y * 100;
+    println!("Part 1: {}", totals[0]);
+    println!("Part 2: {}", totals[1]);
+
+    if let Some(smudges) = args.smudges {
+        println!("With {} smudges: {}", smudges, totals[2]);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Axis {
+    // Between rows `position - 1` and `position`
+    Horizontal,
+    // Between columns `position - 1` and `position`
+    Vertical,
+}
+
+struct Reflection {
+    axis: Axis,
+    position: usize,
+    // How many pairs of mirrored cells differ
+    mismatches: usize,
+}
+
+impl Reflection {
+    fn score(&self) -> usize {
+        match self.axis {
+            Axis::Horizontal => self.position * 100,
+            Axis::Vertical => self.position,
         }
     }
+}
 
-    for x in 1..grid[0].len() {
-        let mut left_index = if x <= grid[0].len() / 2 {
-            0
-        } else {
-            x - (grid[0].len() - x)
-        };
-        let mut right_index = if x > grid[0].len() / 2 {
-            grid[0].len() - 1
-        } else {
-            (x * 2) - 1
+impl std::fmt::Display for Reflection {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let axis = match self.axis {
+            Axis::Horizontal => "y",
+            Axis::Vertical => "x",
         };
+        write!(
+            f,
+            "{} = {}, {} mismatches",
+            axis, self.position, self.mismatches
+        )
+    }
+}
 
-        if debug {
-            println!(
-                "Testing x = {} with left = {}, right = {}",
-                x, left_index, right_index
-            );
-        }
+#[derive(Debug, PartialEq, Eq)]
+enum ReflectionError {
+    EmptyPattern,
+    RaggedPattern { row: usize },
+    NoReflection { smudges: usize },
+}
 
-        let mut mismatch_count = 0;
-        while left_index < right_index {
-            for y in 0..grid.len() {
-                if grid[y][left_index] != grid[y][right_index] {
-                    mismatch_count += 1;
-                }
+impl std::fmt::Display for ReflectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ReflectionError::EmptyPattern => write!(f, "Pattern is empty"),
+            ReflectionError::RaggedPattern { row } => {
+                write!(f, "Row {} is a different length to the first", row)
+            }
+            ReflectionError::NoReflection { smudges } => {
+                write!(f, "No reflection with exactly {} smudges", smudges)
             }
-            left_index += 1;
-            right_index -= 1;
         }
+    }
+}
+
+// Each row and each column packed into bits, 64 cells to a word, with # as 1. The number of
+// cells two lines differ by is then the popcount of their XOR.
+struct Pattern {
+    rows: Vec<Vec<u64>>,
+    columns: Vec<Vec<u64>>,
+}
+
+impl Pattern {
+    fn parse(grid: &[Vec<char>]) -> Result<Self, ReflectionError> {
+        let width = grid.first().map_or(0, |row| row.len());
+        if width == 0 {
+            return Err(ReflectionError::EmptyPattern);
+        }
+        if let Some(row) = grid.iter().position(|row| row.len() != width) {
+            return Err(ReflectionError::RaggedPattern { row: row + 1 });
+        }
+
+        Ok(Pattern {
+            rows: grid
+                .iter()
+                .map(|row| pack(row.iter().map(|c| *c == '#')))
+                .collect(),
+            columns: (0..width)
+                .map(|x| pack(grid.iter().map(|row| row[x] == '#')))
+                .collect(),
+        })
+    }
 
-        if (with_smudge && mismatch_count == 1) || (!with_smudge && mismatch_count == 0) {
-            if debug {
-                println!("x == {}", x);
+    // Every horizontal axis, top to bottom, then every vertical axis, left to right
+    fn reflections(&self) -> Vec<Reflection> {
+        let horizontal = (1..self.rows.len()).map(|y| Reflection {
+            axis: Axis::Horizontal,
+            position: y,
+            mismatches: mismatches_across(&self.rows, y),
+        });
+        let vertical = (1..self.columns.len()).map(|x| Reflection {
+            axis: Axis::Vertical,
+            position: x,
+            mismatches: mismatches_across(&self.columns, x),
+        });
+
+        horizontal.chain(vertical).collect()
+    }
+
+    // Pairs of mirrored cells, as (x, y), that differ across the axis. Changing either cell of
+    // each pair makes the reflection exact.
+    fn mismatched_cells(&self, reflection: &Reflection) -> Vec<[(usize, usize); 2]> {
+        let lines = match reflection.axis {
+            Axis::Horizontal => &self.rows,
+            Axis::Vertical => &self.columns,
+        };
+
+        let mut cells = Vec::new();
+        for (before, after) in mirrored_pairs(lines.len(), reflection.position) {
+            for (word_index, (a, b)) in lines[before].iter().zip(lines[after].iter()).enumerate() {
+                let mut differences = a ^ b;
+                while differences != 0 {
+                    let along = word_index * 64 + differences.trailing_zeros() as usize;
+                    differences &= differences - 1;
+                    cells.push(match reflection.axis {
+                        Axis::Horizontal => [(along, before), (along, after)],
+                        Axis::Vertical => [(before, along), (after, along)],
+                    });
+                }
             }
-            return x;
         }
+
+        cells
     }
+}
 
-    panic!("No reflection found!");
+fn pack(cells: impl Iterator<Item = bool>) -> Vec<u64> {
+    cells
+        .chunks(64)
+        .into_iter()
+        .map(|chunk| {
+            chunk
+                .enumerate()
+                .fold(0, |word, (bit, is_set)| word | (u64::from(is_set) << bit))
+        })
+        .collect()
+}
+
+// Lines paired up outwards from the axis before `position` until one side runs out
+fn mirrored_pairs(count: usize, position: usize) -> impl Iterator<Item = (usize, usize)> {
+    (0..position).rev().zip(position..count)
+}
+
+fn mismatches_across(lines: &[Vec<u64>], position: usize) -> usize {
+    mirrored_pairs(lines.len(), position)
+        .map(|(before, after)| {
+            lines[before]
+                .iter()
+                .zip(lines[after].iter())
+                .map(|(a, b)| (a ^ b).count_ones() as usize)
+                .sum::<usize>()
+        })
+        .sum()
+}
+
+// The first axis, horizontal ones first, that needs exactly `smudges` cells fixed
+fn find_reflection(
+    reflections: &[Reflection],
+    smudges: usize,
+) -> Result<&Reflection, ReflectionError> {
+    reflections
+        .iter()
+        .find(|reflection| reflection.mismatches == smudges)
+        .ok_or(ReflectionError::NoReflection { smudges })
 }
@@ -1,10 +1,8 @@
 use clap::Parser;
-use itertools::Itertools;
 use std::{
     collections::HashMap,
     fs::File,
     io::{BufRead, BufReader},
-    iter,
 };
 
 #[derive(Parser, Debug)]
@@ -16,6 +14,8 @@ struct Args {
     debug: bool,
 }
 
+const SPIN_CYCLES: usize = 1000000000;
+
 fn main() {
     let args = Args::parse();
 
@@ -26,208 +26,244 @@ fn main() {
         .map(|line| line.expect("Failed to read line"))
         .collect();
 
-    let grid = lines
-        .iter()
-        .map(|line| line.chars().collect_vec())
-        .collect_vec();
-
-    // Part 1 is just slide north, easiest to reverse the grid and do slide south
-    let mut part1_grid = grid.clone();
-    let mut part1 = 0;
-    part1_grid.reverse();
-    for y in 0..part1_grid.len() {
-        for x in 0..part1_grid[0].len() {
-            if part1_grid[y][x] == 'O' {
-                if args.debug {
-                    println!("Processing rock at {}, {}", x, y);
-                }
-                let mut additional_rocks = 0;
-                let mut processed = false;
-                for i in (y + 1)..part1_grid.len() {
-                    if part1_grid[i][x] == 'O' {
-                        additional_rocks += 1;
-                    } else if part1_grid[i][x] == '#' {
-                        if args.debug {
-                            println!(
-                                "{}, {} -> {}, {} with {} additional rocks",
-                                x, y, x, i, additional_rocks
-                            );
-                        }
-                        part1 += i - additional_rocks;
-                        processed = true;
-                        break;
-                    }
-                }
+    let platform = Platform::parse(&lines);
 
-                if !processed {
-                    if args.debug {
-                        println!(
-                            "{}, {} -> {}, {} with {} additional rocks",
-                            x, y, x, 0, additional_rocks
-                        );
-                    }
-                    part1 += part1_grid.len() - additional_rocks;
-                }
-            }
+    let mut part1_platform = platform.clone();
+    part1_platform.tilt(Direction::North);
+    if args.debug {
+        println!("{}", part1_platform);
+    }
+    println!("Part 1: {}", part1_platform.north_load());
+
+    // Spin until the platform repeats a position, then skip every whole loop that still fits
+    let mut platform = platform;
+    let mut seen: HashMap<Platform, usize> = HashMap::new();
+    let mut iteration = 0;
+    while iteration < SPIN_CYCLES {
+        if let Some(previous) = seen.insert(platform.clone(), iteration) {
+            let loop_size = iteration - previous;
             if args.debug {
-                println!("Score: {}", part1);
+                println!("Loop detected of size {} at {}", loop_size, iteration);
+            }
+            iteration += (SPIN_CYCLES - iteration) / loop_size * loop_size;
+            seen.clear();
+            if iteration == SPIN_CYCLES {
+                break;
             }
         }
-    }
 
-    println!("Part 1: {}", part1);
+        platform.spin_cycle();
+        iteration += 1;
 
-    let mut grid = grid.clone();
-    let mut history: HashMap<Vec<Vec<char>>, Vec<Vec<char>>> = HashMap::new();
-    let mut iteration = 0;
-    'outer: while iteration < 1000000000 {
         if args.debug {
-            println!("-----------------");
-            println!("Loop {}", iteration);
-            for y in 0..grid.len() {
-                for x in 0..grid[0].len() {
-                    print!("{}", grid[y][x]);
-                }
-                println!();
-            }
-
-            println!("Score: {}", score(&grid));
+            println!("After {} cycles, load {}", iteration, platform.north_load());
+            println!("{}", platform);
         }
-        let mut grid_ref = &grid;
-        let original_iteration = iteration;
-        while history.contains_key(grid_ref) && iteration < 1000000000 {
-            grid_ref = history.get(grid_ref).unwrap();
-            if args.debug {
-                println!("History match");
-            }
-            iteration += 1;
+    }
 
-            if *grid_ref == grid {
-                let loop_size = iteration - original_iteration;
-                if args.debug {
-                    println!("Loop detected of size {}", loop_size);
-                }
+    println!("Part 2: {}", platform.north_load());
+}
 
-                if loop_size == 0 {
-                    break 'outer;
-                }
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Direction {
+    North,
+    West,
+    South,
+    East,
+}
+
+// Rounded rocks and cube rocks as bitboards. Each row is `words` u64s with x as the bit index,
+// so tilting north or south moves 64 columns at once and tilting east or west works a word at a
+// time along each run between cube rocks.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Platform {
+    width: usize,
+    height: usize,
+    words: usize,
+    rounded: Vec<u64>,
+    cubes: Vec<u64>,
+}
 
-                iteration += ((1000000000 - iteration) / loop_size) * loop_size + 1;
+impl Platform {
+    fn parse(lines: &[String]) -> Self {
+        let height = lines.len();
+        let width = lines.first().map_or(0, |line| line.chars().count());
+        let words = width.div_ceil(64);
+
+        let mut platform = Platform {
+            width,
+            height,
+            words,
+            rounded: vec![0; words * height],
+            cubes: vec![0; words * height],
+        };
+        for (y, line) in lines.iter().enumerate() {
+            assert_eq!(
+                line.chars().count(),
+                width,
+                "Row {} is a different length",
+                y
+            );
+            for (x, c) in line.chars().enumerate() {
+                let (word, bit) = (y * words + x / 64, 1 << (x % 64));
+                match c {
+                    'O' => platform.rounded[word] |= bit,
+                    '#' => platform.cubes[word] |= bit,
+                    '.' => {}
+                    _ => panic!("Unknown tile {} at {}, {}", c, x, y),
+                }
             }
         }
-        grid = grid_ref.clone();
-
-        let old_grid = grid.clone();
-        slide_rocks(&mut grid);
-        history.insert(old_grid, grid.clone());
 
-        iteration += 1;
+        platform
     }
 
-    let part2 = score(&grid);
-    println!("Part 2: {}", part2);
-}
+    fn get(&self, x: usize, y: usize) -> char {
+        let (word, bit) = (y * self.words + x / 64, 1 << (x % 64));
+        if self.rounded[word] & bit != 0 {
+            'O'
+        } else if self.cubes[word] & bit != 0 {
+            '#'
+        } else {
+            '.'
+        }
+    }
 
-fn slide_rocks(grid: &mut Vec<Vec<char>>) {
-    for x in 0..grid[0].len() {
-        let mut slide_to_index = 0;
-        let mut rocks = 0;
-        let mut index = 0;
-
-        while index <= grid.len() {
-            if index < grid.len() && grid[index][x] == 'O' {
-                rocks += 1;
-                grid[index][x] = '.'
-            } else if index == grid.len() || grid[index][x] == '#' {
-                for i in 0..rocks {
-                    grid[slide_to_index + i][x] = 'O';
-                }
-                rocks = 0;
-                slide_to_index = index + 1;
-            }
+    fn spin_cycle(&mut self) {
+        for direction in [
+            Direction::North,
+            Direction::West,
+            Direction::South,
+            Direction::East,
+        ] {
+            self.tilt(direction);
+        }
+    }
 
-            index += 1;
+    fn tilt(&mut self, direction: Direction) {
+        match direction {
+            Direction::North => self.tilt_vertically((0..self.height).collect()),
+            Direction::South => self.tilt_vertically((0..self.height).rev().collect()),
+            Direction::West => self.tilt_horizontally(false),
+            Direction::East => self.tilt_horizontally(true),
         }
     }
 
-    for y in 0..grid.len() {
-        let mut slide_to_index = 0;
-        let mut rocks = 0;
-        let mut x = 0;
-
-        while x <= grid[0].len() {
-            if x < grid.len() && grid[y][x] == 'O' {
-                rocks += 1;
-                grid[y][x] = '.'
-            } else if x == grid.len() || grid[y][x] == '#' {
-                for i in 0..rocks {
-                    grid[y][slide_to_index + i] = 'O';
+    // Rows in `order` start at the side the rocks roll towards. Every row before the current one
+    // is already settled, so each word of rocks steps back through them until it's blocked, with
+    // rocks that get stuck dropping out of the word as it goes.
+    fn tilt_vertically(&mut self, order: Vec<usize>) {
+        for (index, y) in order.iter().enumerate().skip(1) {
+            for word in 0..self.words {
+                let mut row = *y;
+                let mut moving = self.rounded[row * self.words + word];
+                for next in order[..index].iter().rev() {
+                    let next_word = next * self.words + word;
+                    moving &= !(self.rounded[next_word] | self.cubes[next_word]);
+                    if moving == 0 {
+                        break;
+                    }
+                    self.rounded[row * self.words + word] &= !moving;
+                    self.rounded[next_word] |= moving;
+                    row = *next;
                 }
-                rocks = 0;
-                slide_to_index = x + 1;
             }
-
-            x += 1;
         }
     }
 
-    grid.reverse();
-    for x in 0..grid[0].len() {
-        let mut slide_to_index = 0;
-        let mut rocks = 0;
-        let mut index = 0;
-
-        while index <= grid.len() {
-            if index < grid.len() && grid[index][x] == 'O' {
-                rocks += 1;
-                grid[index][x] = '.'
-            } else if index == grid.len() || grid[index][x] == '#' {
-                for i in 0..rocks {
-                    grid[slide_to_index + i][x] = 'O';
+    // Within each run between cube rocks, counts the rounded rocks and packs them against the
+    // west end, or the east end if `east` is set
+    fn tilt_horizontally(&mut self, east: bool) {
+        for y in 0..self.height {
+            let range = y * self.words..(y + 1) * self.words;
+            let cubes = &self.cubes[range.clone()];
+            let rounded = &mut self.rounded[range];
+
+            let mut start = 0;
+            while start < self.width {
+                let end = next_set_bit(cubes, start).unwrap_or(self.width);
+                let rocks = count_bits(rounded, start, end);
+                set_bits(rounded, start, end, false);
+                if east {
+                    set_bits(rounded, end - rocks, end, true);
+                } else {
+                    set_bits(rounded, start, start + rocks, true);
                 }
-                rocks = 0;
-                slide_to_index = index + 1;
+                start = end + 1;
             }
-
-            index += 1;
         }
     }
-    grid.reverse();
-
-    for y in 0..grid.len() {
-        let mut slide_to_index = 0;
-        let mut rocks = 0;
-        let mut x = 0;
-
-        // Make positive be west
-        grid[y].reverse();
-        while x <= grid[0].len() {
-            if x < grid.len() && grid[y][x] == 'O' {
-                rocks += 1;
-                grid[y][x] = '.'
-            } else if x == grid.len() || grid[y][x] == '#' {
-                for i in 0..rocks {
-                    grid[y][slide_to_index + i] = 'O';
-                }
-                rocks = 0;
-                slide_to_index = x + 1;
+
+    fn north_load(&self) -> usize {
+        self.rounded
+            .chunks(self.words.max(1))
+            .enumerate()
+            .map(|(y, row)| {
+                row.iter()
+                    .map(|word| word.count_ones() as usize)
+                    .sum::<usize>()
+                    * (self.height - y)
+            })
+            .sum()
+    }
+}
+
+impl std::fmt::Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                write!(f, "{}", self.get(x, y))?;
             }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
 
-            x += 1;
+// The bits of word `word` that fall in start..end
+fn word_mask(word: usize, start: usize, end: usize) -> u64 {
+    let low = start.saturating_sub(word * 64).min(64);
+    let high = end.saturating_sub(word * 64).min(64);
+    let below = |bits: usize| {
+        if bits == 64 {
+            u64::MAX
+        } else {
+            (1 << bits) - 1
         }
-        grid[y].reverse();
+    };
+
+    below(high) & !below(low)
+}
+
+fn words_in(start: usize, end: usize) -> std::ops::Range<usize> {
+    if start >= end {
+        0..0
+    } else {
+        start / 64..end.div_ceil(64)
     }
 }
 
-fn score(grid: &Vec<Vec<char>>) -> usize {
-    let mut score = 0;
-    for y in 0..grid.len() {
-        for x in 0..grid[0].len() {
-            if grid[y][x] == 'O' {
-                score += grid.len() - y;
-            }
+fn count_bits(row: &[u64], start: usize, end: usize) -> usize {
+    words_in(start, end)
+        .map(|word| (row[word] & word_mask(word, start, end)).count_ones() as usize)
+        .sum()
+}
+
+fn set_bits(row: &mut [u64], start: usize, end: usize, value: bool) {
+    for word in words_in(start, end) {
+        let mask = word_mask(word, start, end);
+        if value {
+            row[word] |= mask;
+        } else {
+            row[word] &= !mask;
         }
     }
+}
 
-    return score;
+fn next_set_bit(row: &[u64], from: usize) -> Option<usize> {
+    (from / 64..row.len()).find_map(|word| {
+        let bits = row[word] & !word_mask(word, 0, from);
+        (bits != 0).then(|| word * 64 + bits.trailing_zeros() as usize)
+    })
 }